@@ -0,0 +1,342 @@
+//! A map that may be inserted into through a shared reference, like an arena.
+
+use std::{
+    cell::Cell,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ptr,
+};
+use crate::{buckets, error, registry::{self, Claim}, Key, ProvenanceError};
+
+const FOREIGN_KEY: &str = "key were created by another map with the same provenance";
+
+/// A [SeparateProvenanceMap](crate::SeparateProvenanceMap) that is inserted into
+/// through a shared reference.
+///
+/// Values are never moved or removed once inserted, so references retrieved with
+/// [get](AppendOnlyProvenanceMap::get) stay valid while more values are inserted.
+/// This makes the map suitable as an arena.
+/// ```
+/// use provenance::{AppendOnlyProvenanceMap, Key};
+///
+/// struct Node { name: &'static str, parent: Option<Key<Nodes>> }
+/// struct Nodes;
+///
+/// let nodes = AppendOnlyProvenanceMap::<Nodes, Node>::new().unwrap();
+///
+/// let root_key = nodes.insert(Node { name: "root", parent: None });
+/// let root = nodes.get(root_key);
+///
+/// // Inserting does not invalidate `root`
+/// let leaf = nodes.get(nodes.insert(Node { name: "leaf", parent: Some(root_key) }));
+///
+/// assert_eq!("root", root.name);
+/// assert_eq!("root", nodes.get(leaf.parent.unwrap()).name);
+/// ```
+///
+/// The map can not be shared between threads, see
+/// [ConcurrentProvenanceMap](crate::ConcurrentProvenanceMap) for a map that can.
+pub struct AppendOnlyProvenanceMap<Provenance, Value> {
+    buckets: [Cell<*mut MaybeUninit<Value>>; buckets::BUCKETS],
+    len: Cell<usize>,
+    /// Shared by every key of this map, as values never are removed.
+    generation: u64,
+    // Only held to release the provenance when the map is dropped.
+    _claim: Claim,
+    _pd: PhantomData<fn() -> Provenance>,
+    _values: PhantomData<Value>,
+}
+
+// The buckets are owned by the map, so it may be sent to another thread with its values.
+unsafe impl<Provenance, Value: Send> Send for AppendOnlyProvenanceMap<Provenance, Value> {}
+
+impl<Provenance: 'static, Value: 'static> AppendOnlyProvenanceMap<Provenance, Value> {
+
+    /// Creates a new empty map with some type as provenance.
+    ///
+    /// If a map with such provenance already exists, [`None`](std::option::Option::None) will be returned.
+    /// ```
+    /// use provenance::AppendOnlyProvenanceMap;
+    /// struct Provenance;
+    ///
+    /// let map = AppendOnlyProvenanceMap::<Provenance, i32>::new();
+    /// assert!(map.is_some());
+    ///
+    /// let map = AppendOnlyProvenanceMap::<Provenance, i32>::new();
+    /// assert!(map.is_none());
+    /// ```
+    pub fn new() -> Option<AppendOnlyProvenanceMap<Provenance, Value>> {
+        AppendOnlyProvenanceMap::try_new().ok()
+    }
+
+    /// Creates a new empty map with some type as provenance.
+    ///
+    /// If a map with such provenance already exists, or the map could not be created
+    /// for another reason, an [error](ProvenanceError) describing why is returned.
+    /// ```
+    /// use provenance::{AppendOnlyProvenanceMap, ProvenanceError};
+    /// struct Provenance;
+    ///
+    /// let map = AppendOnlyProvenanceMap::<Provenance, i32>::try_new();
+    /// assert!(map.is_ok());
+    ///
+    /// let map = AppendOnlyProvenanceMap::<Provenance, i32>::try_new();
+    /// assert!(matches!(map, Err(ProvenanceError::InUse { .. })));
+    /// ```
+    pub fn try_new() -> Result<AppendOnlyProvenanceMap<Provenance, Value>, ProvenanceError> {
+        let claim = Claim::acquire::<Provenance>()?;
+
+        Ok(AppendOnlyProvenanceMap {
+            buckets: std::array::from_fn(|_| Cell::new(ptr::null_mut())),
+            len: Cell::new(0),
+            generation: registry::fresh_generation(),
+            _claim: claim,
+            _pd: Default::default(),
+            _values: Default::default(),
+        })
+    }
+
+    /// Insert a value into this map.
+    /// A unique key is returned. The key may be used to retrieve the value.
+    ///
+    /// Only a shared reference to the map is needed, so references to
+    /// previously inserted values remain usable.
+    /// ```
+    /// use provenance::AppendOnlyProvenanceMap;
+    /// struct Provenance;
+    /// let map = AppendOnlyProvenanceMap::<Provenance, String>::new().unwrap();
+    ///
+    /// let hello = map.get(map.insert("Hello".to_string()));
+    /// let world = map.get(map.insert("world".to_string()));
+    /// assert_eq!("Hello world", format!("{} {}", hello, world));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the map has no room for another value, see
+    /// [try_insert](AppendOnlyProvenanceMap::try_insert).
+    pub fn insert(&self, value: Value) -> Key<Provenance> {
+        self.try_insert(value).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Insert a value into this map, unless the map has no room for another value.
+    /// ```
+    /// use provenance::AppendOnlyProvenanceMap;
+    /// struct Provenance;
+    /// let map = AppendOnlyProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.try_insert(5).unwrap();
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    pub fn try_insert(&self, value: Value) -> Result<Key<Provenance>, ProvenanceError> {
+        let index = self.len.get();
+        if index == buckets::CAPACITY {
+            return Err(error::capacity_exceeded::<Provenance>());
+        }
+        let (bucket, offset) = buckets::locate(index);
+
+        let mut values = self.buckets[bucket].get();
+        if values.is_null() {
+            values = buckets::allocate(bucket, MaybeUninit::uninit);
+            self.buckets[bucket].set(values);
+        }
+
+        // Buckets are allocated with their full length, and the value at `index`
+        // has not been initialised, so no reference to it exists.
+        unsafe { (*values.add(offset)).write(value); }
+        self.len.set(index + 1);
+
+        Ok(Key::new(index, self.generation))
+    }
+
+    /// Use a [key](Key) to retrieve an immutable reference to a stored value.
+    /// ```
+    /// use provenance::AppendOnlyProvenanceMap;
+    /// struct Provenance;
+    /// let map = AppendOnlyProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the key were created by an earlier, since dropped, map with the same provenance.
+    pub fn get(&self, key: Key<Provenance>) -> &Value {
+        // Values are never mutated through a shared reference to the map.
+        unsafe { &*self.value(key) }
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to a stored value.
+    /// ```
+    /// use provenance::AppendOnlyProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = AppendOnlyProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// *map.get_mut(key) += 1;
+    /// assert_eq!(&6, map.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the key were created by an earlier, since dropped, map with the same provenance.
+    pub fn get_mut(&mut self, key: Key<Provenance>) -> &mut Value {
+        // The map is mutably borrowed, so no other reference to the value exists.
+        unsafe { &mut *self.value(key) }
+    }
+
+    /// Get an [iterator](Iterator) over all keys in the map.
+    /// ```
+    /// use provenance::AppendOnlyProvenanceMap;
+    /// struct Provenance;
+    /// let map = AppendOnlyProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.insert(3);
+    ///
+    /// assert_eq!(3, map.keys().count());
+    /// ```
+    pub fn keys(&self) -> impl Iterator<Item = Key<Provenance>> {
+        let generation = self.generation;
+        (0..self.len.get())
+            .map(move |index| Key::new(index, generation))
+    }
+
+    /// Get an [iterator](Iterator) over immutable references to each value in the map.
+    /// ```
+    /// use provenance::AppendOnlyProvenanceMap;
+    /// struct Provenance;
+    /// let map = AppendOnlyProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.insert(3);
+    ///
+    /// assert_eq!(6, map.iter().sum());
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = &Value> {
+        let len = self.len.get();
+
+        self.buckets.iter()
+            .enumerate()
+            .flat_map(move |(bucket, values)| {
+                let count = Self::initialised(bucket, len);
+                let values: &[MaybeUninit<Value>] = if count == 0 {
+                    &[]
+                } else {
+                    // The first `count` values of the bucket are initialised.
+                    unsafe { std::slice::from_raw_parts(values.get(), count) }
+                };
+
+                // Values are never mutated through a shared reference to the map.
+                values.iter().map(|value| unsafe { value.assume_init_ref() })
+            })
+    }
+
+    /// Get an [iterator](Iterator) over mutable references to each value in the map.
+    /// ```
+    /// use provenance::AppendOnlyProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = AppendOnlyProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.insert(3);
+    ///
+    /// // Add one to every value
+    /// map.iter_mut().for_each(|val| *val += 1);
+    ///
+    /// assert_eq!(9, map.iter().sum());
+    /// ```
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        let len = self.len.get();
+
+        self.buckets.iter_mut()
+            .enumerate()
+            .flat_map(move |(bucket, values)| {
+                let count = Self::initialised(bucket, len);
+                let values: &mut [MaybeUninit<Value>] = if count == 0 {
+                    &mut []
+                } else {
+                    // The first `count` values of the bucket are initialised, and
+                    // the map is mutably borrowed for as long as the iterator lives.
+                    unsafe { std::slice::from_raw_parts_mut(values.get(), count) }
+                };
+
+                values.iter_mut().map(|value| unsafe { value.assume_init_mut() })
+            })
+    }
+
+    /// Search the map in insertion order for the first value that satisfy the given predicate.
+    /// If such value is found, an immutable reference to it is returned,
+    /// otherwise [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// use provenance::AppendOnlyProvenanceMap;
+    /// struct Provenance;
+    /// let map = AppendOnlyProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.insert(3);
+    ///
+    /// assert_eq!(Some(&2), map.find(|&val| val == 2));
+    /// assert_eq!(None, map.find(|&val| val == 53));
+    /// ```
+    pub fn find<P: Fn(&Value) -> bool>(&self, predicate: P) -> Option<&Value> {
+        self.iter().find(|value| predicate(value))
+    }
+
+    /// Search the map in insertion order for the first value that satisfy the given predicate.
+    /// If such value is found, a mutable reference to it is returned,
+    /// otherwise [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// use provenance::AppendOnlyProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = AppendOnlyProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.insert(3);
+    ///
+    /// assert_eq!(Some(&mut 2), map.find_mut(|&val| val == 2));
+    /// assert_eq!(None, map.find_mut(|&val| val == 53));
+    /// ```
+    pub fn find_mut<P: Fn(&Value) -> bool>(&mut self, predicate: P) -> Option<&mut Value> {
+        self.iter_mut().find(|value| predicate(value))
+    }
+
+    /// Number of initialised values in a bucket, given the length of the map.
+    fn initialised(bucket: usize, len: usize) -> usize {
+        len.saturating_sub(buckets::bucket_start(bucket))
+            .min(buckets::bucket_len(bucket))
+    }
+
+    /// A pointer to the value of a key.
+    fn value(&self, key: Key<Provenance>) -> *mut Value {
+        assert_eq!(self.generation, key.generation, "{}", FOREIGN_KEY);
+
+        // The key has the correct provenance and generation,
+        // thus we know that we created it in `insert`,
+        // thus its value is initialised.
+        let (bucket, offset) = buckets::locate(key.index);
+        unsafe { (*self.buckets[bucket].get().add(offset)).as_mut_ptr() }
+    }
+}
+
+impl<Provenance, Value> Drop for AppendOnlyProvenanceMap<Provenance, Value> {
+    fn drop(&mut self) {
+        for index in 0..self.len.get() {
+            let (bucket, offset) = buckets::locate(index);
+
+            // Every value below `len` is initialised.
+            unsafe { (*self.buckets[bucket].get().add(offset)).assume_init_drop(); }
+        }
+
+        for (bucket, values) in self.buckets.iter().enumerate() {
+            if !values.get().is_null() {
+                // The values have been dropped above, and freeing their bucket does
+                // not drop them again.
+                unsafe { buckets::free(values.get(), bucket); }
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Maps whose provenance is a unique lifetime rather than a unique type.
+
+use std::marker::PhantomData;
+use crate::Key;
+
+/// The provenance of a [BrandedMap](BrandedMap).
+///
+/// `'id` is a lifetime that is unique to a single invocation of [scope](scope).
+/// The brand is invariant over `'id`, so the compiler can neither shorten nor
+/// lengthen it to make it match the brand of another map.
+pub struct Brand<'id> {
+    _pd: PhantomData<fn(&'id ()) -> &'id ()>,
+}
+
+/// A key generated by a [BrandedMap](BrandedMap).
+pub type BrandedKey<'id> = Key<Brand<'id>>;
+
+/// Create a [BrandedMap](BrandedMap) that only lives for the duration of the given closure.
+///
+/// Each invocation of `scope` brands its map with a fresh lifetime, so unlike
+/// [SeparateProvenanceMap](crate::SeparateProvenanceMap) no global registry is
+/// needed and creating a map can not fail. Any number of maps may store values
+/// of the same type.
+/// ```
+/// let sum = provenance::scope(|mut map| {
+///     let one = map.insert(1);
+///     let two = map.insert(2);
+///
+///     map.get(one) + map.get(two)
+/// });
+///
+/// assert_eq!(3, sum);
+/// ```
+///
+/// Using a key with a map from another scope is a compile time error:
+/// ```compile_fail
+/// provenance::scope(|mut outer| {
+///     let key = outer.insert(5);
+///
+///     provenance::scope(|mut inner| {
+///         inner.insert(7);
+///         inner.get(key); // The key is branded with the outer map's lifetime
+///     });
+/// });
+/// ```
+///
+/// Neither can keys escape the closure that their map lives in:
+/// ```compile_fail
+/// let key = provenance::scope(|mut map| map.insert(5));
+/// ```
+pub fn scope<Value, R, F>(f: F) -> R
+    where F: for<'id> FnOnce(BrandedMap<'id, Value>) -> R
+{
+    f(BrandedMap {
+        elements: vec![],
+        _brand: Brand { _pd: Default::default() },
+    })
+}
+
+/// A map whose keys are branded with a lifetime unique to the map.
+///
+/// It is created with [scope](scope), and offers the same guarantees as
+/// [SeparateProvenanceMap](crate::SeparateProvenanceMap): a key can only be used
+/// with the map that created it, and retrieving a value never fails.
+pub struct BrandedMap<'id, Value> {
+    elements: Vec<Value>,
+    _brand: Brand<'id>,
+}
+
+impl<'id, Value> BrandedMap<'id, Value> {
+
+    /// Insert a value into this map.
+    /// A unique key is returned. The key may be used to retrieve the value.
+    /// ```
+    /// provenance::scope(|mut map| {
+    ///     let key = map.insert(5);
+    ///     assert_eq!(&5, map.get(key));
+    /// });
+    /// ```
+    ///
+    /// Values are not required to be unique in the map.
+    /// ```
+    /// provenance::scope(|mut map| {
+    ///     let key1 = map.insert(5);
+    ///     let key2 = map.insert(5);
+    ///     assert_ne!(key1, key2);
+    /// });
+    /// ```
+    pub fn insert(&mut self, value: Value) -> BrandedKey<'id> {
+        let index = self.elements.len();
+        self.elements.push(value);
+        Key::new(index, 0)
+    }
+
+    /// Use a [key](Key) to retrieve an immutable reference to a stored value.
+    /// ```
+    /// provenance::scope(|mut map| {
+    ///     let key = map.insert(5);
+    ///     assert_eq!(&5, map.get(key));
+    /// });
+    /// ```
+    pub fn get(&self, key: BrandedKey<'id>) -> &Value {
+        // The key has the brand of this map,
+        // thus we know that we created it in `insert`,
+        // thus it is safe to use.
+        &self.elements[key.index]
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to a stored value.
+    /// ```
+    /// provenance::scope(|mut map| {
+    ///     let key = map.insert(5);
+    ///     assert_eq!(&mut 5, map.get_mut(key));
+    /// });
+    /// ```
+    pub fn get_mut(&mut self, key: BrandedKey<'id>) -> &mut Value {
+        // The key has the brand of this map,
+        // thus we know that we created it in `insert`,
+        // thus it is safe to use.
+        &mut self.elements[key.index]
+    }
+
+    /// Get an [iterator](Iterator) over all keys in the map.
+    /// ```
+    /// provenance::scope(|mut map| {
+    ///     map.insert(1);
+    ///     map.insert(2);
+    ///     map.insert(3);
+    ///
+    ///     assert_eq!(3, map.keys().count());
+    /// });
+    /// ```
+    pub fn keys(&self) -> impl Iterator<Item = BrandedKey<'id>> {
+        (0..self.elements.len())
+            .map(|index| Key::new(index, 0))
+    }
+
+    /// Get an [iterator](Iterator) over immutable references to each value in the map.
+    /// ```
+    /// provenance::scope(|mut map| {
+    ///     map.insert(1);
+    ///     map.insert(2);
+    ///     map.insert(3);
+    ///
+    ///     assert_eq!(6, map.iter().sum::<i32>());
+    /// });
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = &Value> {
+        self.elements.iter()
+    }
+
+    /// Get an [iterator](Iterator) over mutable references to each value in the map.
+    /// ```
+    /// provenance::scope(|mut map| {
+    ///     map.insert(1);
+    ///     map.insert(2);
+    ///     map.insert(3);
+    ///
+    ///     // Add one to every value
+    ///     map.iter_mut().for_each(|val| *val += 1);
+    ///
+    ///     assert_eq!(9, map.iter().sum::<i32>());
+    /// });
+    /// ```
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.elements.iter_mut()
+    }
+
+    /// Search the map in insertion order for the first value that satisfy the given predicate.
+    /// If such value is found, an immutable reference to it is returned,
+    /// otherwise [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// provenance::scope(|mut map| {
+    ///     map.insert(1);
+    ///     map.insert(2);
+    ///     map.insert(3);
+    ///
+    ///     assert_eq!(Some(&2), map.find(|&val| val == 2));
+    ///     assert_eq!(None, map.find(|&val| val == 53));
+    /// });
+    /// ```
+    pub fn find<P: Fn(&Value) -> bool>(&self, predicate: P) -> Option<&Value> {
+        self.elements.iter().find(|value| predicate(value))
+    }
+
+    /// Search the map in insertion order for the first value that satisfy the given predicate.
+    /// If such value is found, a mutable reference to it is returned,
+    /// otherwise [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// provenance::scope(|mut map| {
+    ///     map.insert(1);
+    ///     map.insert(2);
+    ///     map.insert(3);
+    ///
+    ///     assert_eq!(Some(&mut 2), map.find_mut(|&val| val == 2));
+    ///     assert_eq!(None, map.find_mut(|&val| val == 53));
+    /// });
+    /// ```
+    pub fn find_mut<P: Fn(&Value) -> bool>(&mut self, predicate: P) -> Option<&mut Value> {
+        self.elements.iter_mut().find(|value| predicate(value))
+    }
+}
@@ -0,0 +1,89 @@
+//! Layout of storage split into buckets of increasing length.
+//!
+//! Bucket `b` has room for `FIRST_BUCKET_LEN << b` values, so a fixed number of
+//! buckets can hold any number of values that an `usize` can index. Since
+//! buckets never are reallocated, values stored in them never move.
+
+use std::ptr;
+
+/// Length of the first bucket. Must be a power of two.
+const FIRST_BUCKET_LEN: usize = 32;
+
+/// Number of buckets needed for every `usize` to be a valid index.
+pub(crate) const BUCKETS: usize = (usize::BITS - FIRST_BUCKET_LEN.trailing_zeros()) as usize;
+
+/// Number of values the buckets together have room for.
+pub(crate) const CAPACITY: usize = usize::MAX - FIRST_BUCKET_LEN + 1;
+
+/// Number of values a bucket has room for.
+pub(crate) fn bucket_len(bucket: usize) -> usize {
+    FIRST_BUCKET_LEN << bucket
+}
+
+/// Index of the first value in a bucket.
+pub(crate) fn bucket_start(bucket: usize) -> usize {
+    bucket_len(bucket) - FIRST_BUCKET_LEN
+}
+
+/// Find the bucket, and the offset within that bucket, of an index below [CAPACITY](CAPACITY).
+pub(crate) fn locate(index: usize) -> (usize, usize) {
+    debug_assert!(index < CAPACITY, "index beyond the capacity of the buckets");
+    let biased = index + FIRST_BUCKET_LEN;
+    let bucket = (biased.ilog2() - FIRST_BUCKET_LEN.trailing_zeros()) as usize;
+
+    (bucket, biased - bucket_len(bucket))
+}
+
+/// Allocate a bucket, initialising each element with the given function.
+pub(crate) fn allocate<T>(bucket: usize, init: impl FnMut() -> T) -> *mut T {
+    let elements: Box<[T]> = std::iter::repeat_with(init)
+        .take(bucket_len(bucket))
+        .collect();
+
+    Box::into_raw(elements) as *mut T
+}
+
+/// Free a bucket allocated by [allocate](allocate), dropping its elements.
+///
+/// # Safety
+/// `elements` must have been returned by [allocate](allocate) for the same bucket,
+/// and must not be used afterwards.
+pub(crate) unsafe fn free<T>(elements: *mut T, bucket: usize) {
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(elements, bucket_len(bucket))));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_crosses_bucket_boundaries() {
+        assert_eq!((0, 0), locate(0));
+        assert_eq!((0, 31), locate(31));
+        assert_eq!((1, 0), locate(32));
+        assert_eq!((1, 63), locate(95));
+        assert_eq!((2, 0), locate(96));
+        assert_eq!((2, 127), locate(223));
+        assert_eq!((3, 0), locate(224));
+    }
+
+    #[test]
+    fn locate_is_consistent_with_bucket_start() {
+        for bucket in 0..BUCKETS - 1 {
+            let start = bucket_start(bucket);
+            let end = start + bucket_len(bucket) - 1;
+
+            assert_eq!((bucket, 0), locate(start));
+            assert_eq!((bucket, bucket_len(bucket) - 1), locate(end));
+            assert_eq!(bucket_start(bucket + 1), end + 1);
+        }
+    }
+
+    #[test]
+    fn locate_reaches_the_last_bucket() {
+        let last = BUCKETS - 1;
+
+        assert_eq!((last, 0), locate(bucket_start(last)));
+        assert_eq!((last, bucket_len(last) - 1), locate(CAPACITY - 1));
+    }
+}
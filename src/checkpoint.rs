@@ -0,0 +1,263 @@
+//! Speculative insertion into maps, which may be undone.
+
+use std::marker::PhantomData;
+use crate::{Key, ProvenanceMap, SeparateProvenanceMap};
+
+/// The provenance of keys created through a [Checkpoint](Checkpoint) or a
+/// [Transaction](crate::Transaction).
+///
+/// `'cp` is a lifetime unique to a single checkpoint or transaction, like the brand
+/// of a [BrandedMap](crate::BrandedMap), so such keys can not be used after it has ended.
+pub struct Speculative<'cp, Provenance> {
+    _brand: PhantomData<fn(&'cp ()) -> &'cp ()>,
+    _pd: PhantomData<fn() -> Provenance>,
+}
+
+/// A key to a value inserted through a [Checkpoint](Checkpoint) or a [Transaction](crate::Transaction).
+pub type SpeculativeKey<'cp, Provenance> = Key<Speculative<'cp, Provenance>>;
+
+/// A point in the history of a map, which values inserted afterwards may be truncated back to.
+///
+/// A checkpoint is created with [SeparateProvenanceMap::checkpoint](SeparateProvenanceMap::checkpoint),
+/// and values are inserted through it. Unless [committed](Checkpoint::commit), every value
+/// inserted through the checkpoint is removed when it is [truncated](Checkpoint::truncate)
+/// or dropped.
+pub struct Checkpoint<'cp, Provenance: 'static, Value: 'static> {
+    map: &'cp mut SeparateProvenanceMap<Provenance, Value>,
+    /// Keys of the values inserted since the checkpoint.
+    inserted: Vec<Key<Provenance>>,
+    _brand: PhantomData<fn(&'cp ()) -> &'cp ()>,
+}
+
+/// The keys of the values of a [committed](Checkpoint::commit) checkpoint or
+/// [transaction](crate::Transaction::commit).
+pub struct Committed<'cp, Provenance> {
+    _brand: PhantomData<fn(&'cp ()) -> &'cp ()>,
+    _pd: PhantomData<fn() -> Provenance>,
+}
+
+impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Value> {
+
+    /// Create a [checkpoint](Checkpoint) that lives for the duration of the given closure.
+    ///
+    /// Values inserted through the checkpoint are removed when it is dropped, unless it
+    /// has been committed. Keys of such values are branded with the checkpoint, so they
+    /// can not be used after it has ended, and thus never outlive their values. Committing
+    /// the checkpoint gives the keys of the map in exchange.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    ///
+    /// struct Tokens;
+    /// let mut tokens = SeparateProvenanceMap::<Tokens, String>::new().unwrap();
+    ///
+    /// fn parse(input: &str, tokens: &mut SeparateProvenanceMap<Tokens, String>) -> Option<Vec<provenance::Key<Tokens>>> {
+    ///     tokens.checkpoint(|mut checkpoint| {
+    ///         let mut keys = vec![];
+    ///         for word in input.split(' ') {
+    ///             if word.is_empty() {
+    ///                 return None; // The tokens of this input are removed
+    ///             }
+    ///             keys.push(checkpoint.insert(word.to_string()));
+    ///         }
+    ///
+    ///         let committed = checkpoint.commit();
+    ///         Some(keys.into_iter().map(|key| committed.key(key)).collect())
+    ///     })
+    /// }
+    ///
+    /// let keys = parse("Hello world", &mut tokens).unwrap();
+    /// assert_eq!("world", tokens.get(keys[1]));
+    ///
+    /// assert!(parse("Hello  world", &mut tokens).is_none());
+    /// assert_eq!(2, tokens.len());
+    /// ```
+    ///
+    /// Keys of values inserted through a checkpoint can not escape it:
+    /// ```compile_fail
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.checkpoint(|mut checkpoint| checkpoint.insert(5));
+    /// ```
+    pub fn checkpoint<R, F>(&mut self, f: F) -> R
+        where F: for<'cp> FnOnce(Checkpoint<'cp, Provenance, Value>) -> R
+    {
+        f(Checkpoint {
+            map: self,
+            inserted: vec![],
+            _brand: Default::default(),
+        })
+    }
+}
+
+impl<Value: 'static> ProvenanceMap<Value> {
+
+    /// Create a [checkpoint](Checkpoint) that lives for the duration of the given closure.
+    /// See [SeparateProvenanceMap::checkpoint](SeparateProvenanceMap::checkpoint).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// map.checkpoint(|mut checkpoint| {
+    ///     checkpoint.insert(1);
+    /// });
+    /// assert!(map.is_empty());
+    /// ```
+    pub fn checkpoint<R, F>(&mut self, f: F) -> R
+        where F: for<'cp> FnOnce(Checkpoint<'cp, Value, Value>) -> R
+    {
+        self.map.checkpoint(f)
+    }
+}
+
+impl<'cp, Provenance: 'static, Value: 'static> Checkpoint<'cp, Provenance, Value> {
+
+    /// Insert a value into the map, until the checkpoint is truncated.
+    /// A key branded with the checkpoint is returned.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.checkpoint(|mut checkpoint| {
+    ///     let key = checkpoint.insert(5);
+    ///     assert_eq!(&5, checkpoint.get(key));
+    /// });
+    /// ```
+    pub fn insert(&mut self, value: Value) -> SpeculativeKey<'cp, Provenance> {
+        let key = self.map.insert(value);
+        self.inserted.push(key);
+
+        Key::new(key.index, key.generation)
+    }
+
+    /// Use a [key](SpeculativeKey) to retrieve an immutable reference to a value
+    /// inserted through the checkpoint.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.checkpoint(|mut checkpoint| {
+    ///     let key = checkpoint.insert(5);
+    ///     assert_eq!(&5, checkpoint.get(key));
+    /// });
+    /// ```
+    pub fn get(&self, key: SpeculativeKey<'cp, Provenance>) -> &Value {
+        self.map.get(Key::new(key.index, key.generation))
+    }
+
+    /// Use a [key](SpeculativeKey) to retrieve a mutable reference to a value
+    /// inserted through the checkpoint.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.checkpoint(|mut checkpoint| {
+    ///     let key = checkpoint.insert(5);
+    ///     *checkpoint.get_mut(key) += 1;
+    ///     assert_eq!(&6, checkpoint.get(key));
+    /// });
+    /// ```
+    pub fn get_mut(&mut self, key: SpeculativeKey<'cp, Provenance>) -> &mut Value {
+        self.map.get_mut(Key::new(key.index, key.generation))
+    }
+
+    /// Use a [key](Key) of the map to retrieve an immutable reference to a value
+    /// that were in the map before the checkpoint.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let five = map.insert(5);
+    /// map.checkpoint(|mut checkpoint| {
+    ///     let six = checkpoint.insert(checkpoint.get_existing(five) + 1);
+    ///     assert_eq!(&6, checkpoint.get(six));
+    /// });
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the key is stale, see [SeparateProvenanceMap::get](SeparateProvenanceMap::get).
+    pub fn get_existing(&self, key: Key<Provenance>) -> &Value {
+        self.map.get(key)
+    }
+
+    /// Truncate the map back to the checkpoint, removing every value inserted through it.
+    ///
+    /// Dropping the checkpoint has the same effect, this only makes it explicit.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.checkpoint(|mut checkpoint| {
+    ///     checkpoint.insert(2);
+    ///     checkpoint.insert(3);
+    ///     checkpoint.truncate();
+    /// });
+    ///
+    /// assert_eq!(1, map.iter().sum());
+    /// ```
+    pub fn truncate(self) {}
+
+    /// End the checkpoint, keeping every value inserted through it.
+    ///
+    /// The returned [Committed](Committed) exchanges the keys of the checkpoint for
+    /// keys of the map, which may be used after the checkpoint has ended.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.checkpoint(|mut checkpoint| {
+    ///     let key = checkpoint.insert(5);
+    ///     checkpoint.commit().key(key)
+    /// });
+    ///
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    pub fn commit(mut self) -> Committed<'cp, Provenance> {
+        self.inserted.clear();
+        Committed::new()
+    }
+}
+
+impl<'cp, Provenance: 'static, Value: 'static> Drop for Checkpoint<'cp, Provenance, Value> {
+    fn drop(&mut self) {
+        for key in self.inserted.drain(..).rev() {
+            self.map.take(key);
+        }
+    }
+}
+
+impl<'cp, Provenance> Committed<'cp, Provenance> {
+
+    /// Only to be created once the changes made with keys branded `'cp` are final.
+    pub(crate) fn new() -> Committed<'cp, Provenance> {
+        Committed {
+            _brand: Default::default(),
+            _pd: Default::default(),
+        }
+    }
+
+    /// Exchange a key of the committed checkpoint for a key of the map.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.checkpoint(|mut checkpoint| {
+    ///     let key = checkpoint.insert(5);
+    ///     checkpoint.commit().key(key)
+    /// });
+    ///
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    pub fn key(&self, key: SpeculativeKey<'cp, Provenance>) -> Key<Provenance> {
+        Key::new(key.index, key.generation)
+    }
+}
@@ -0,0 +1,350 @@
+//! A map that may be inserted into, and read from, by many threads at once.
+
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+};
+use crate::{buckets, error, registry::{self, Claim}, Key, ProvenanceError};
+
+const FOREIGN_KEY: &str = "key were created by another map with the same provenance";
+
+/// A [SeparateProvenanceMap](crate::SeparateProvenanceMap) that can be shared between threads.
+///
+/// Values are inserted through a shared reference, and are never moved or removed
+/// once inserted. Therefore, neither inserting nor retrieving values requires a lock,
+/// and references to values stay valid while more values are inserted.
+/// ```
+/// use provenance::ConcurrentProvenanceMap;
+/// use std::{sync::Arc, thread};
+///
+/// struct Jobs;
+/// let map = Arc::new(ConcurrentProvenanceMap::<Jobs, String>::new().unwrap());
+///
+/// let workers: Vec<_> = (0..4)
+///     .map(|n| {
+///         let map = Arc::clone(&map);
+///         thread::spawn(move || map.insert(format!("job {}", n)))
+///     })
+///     .collect();
+///
+/// for worker in workers {
+///     let key = worker.join().unwrap();
+///     assert!(map.get(key).starts_with("job"));
+/// }
+/// ```
+///
+/// Like other maps, there may only exist one map per provenance at a time, and
+/// the provenance is released when the map is dropped.
+/// ```
+/// use provenance::{ConcurrentProvenanceMap, SeparateProvenanceMap};
+/// struct Provenance;
+///
+/// let map = ConcurrentProvenanceMap::<Provenance, i32>::new();
+/// assert!(map.is_some());
+///
+/// let map = SeparateProvenanceMap::<Provenance, i32>::new();
+/// assert!(map.is_none());
+/// ```
+pub struct ConcurrentProvenanceMap<Provenance, Value> {
+    buckets: [AtomicPtr<Entry<Value>>; buckets::BUCKETS],
+    /// Number of indices handed out to inserts, some of which may not be done yet.
+    reserved: AtomicUsize,
+    /// Shared by every key of this map, as values never are removed.
+    generation: u64,
+    // Only held to release the provenance when the map is dropped.
+    _claim: Claim,
+    _pd: PhantomData<fn() -> Provenance>,
+    // Values are owned by the map, but a raw pointer makes it neither `Send`
+    // nor `Sync` until the manual implementations below says so.
+    _values: PhantomData<*const Value>,
+}
+
+/// Storage for one value. The value is initialised once `ready` is set.
+struct Entry<Value> {
+    ready: AtomicBool,
+    value: UnsafeCell<MaybeUninit<Value>>,
+}
+
+// Values are inserted from, and dropped by, whichever thread has access to the
+// map, hence they must be `Send`. Since references to values are shared between
+// the threads sharing the map, values also must be `Sync` for the map to be.
+unsafe impl<Provenance, Value: Send> Send for ConcurrentProvenanceMap<Provenance, Value> {}
+unsafe impl<Provenance, Value: Send + Sync> Sync for ConcurrentProvenanceMap<Provenance, Value> {}
+
+impl<Provenance: 'static, Value: 'static> ConcurrentProvenanceMap<Provenance, Value> {
+
+    /// Creates a new empty map with some type as provenance.
+    ///
+    /// If a map with such provenance already exists, [`None`](std::option::Option::None) will be returned.
+    /// ```
+    /// use provenance::ConcurrentProvenanceMap;
+    /// struct Provenance;
+    ///
+    /// let map = ConcurrentProvenanceMap::<Provenance, i32>::new();
+    /// assert!(map.is_some());
+    ///
+    /// let map = ConcurrentProvenanceMap::<Provenance, i32>::new();
+    /// assert!(map.is_none());
+    /// ```
+    pub fn new() -> Option<ConcurrentProvenanceMap<Provenance, Value>> {
+        ConcurrentProvenanceMap::try_new().ok()
+    }
+
+    /// Creates a new empty map with some type as provenance.
+    ///
+    /// If a map with such provenance already exists, or the map could not be created
+    /// for another reason, an [error](ProvenanceError) describing why is returned.
+    /// ```
+    /// use provenance::{ConcurrentProvenanceMap, ProvenanceError};
+    /// struct Provenance;
+    ///
+    /// let map = ConcurrentProvenanceMap::<Provenance, i32>::try_new();
+    /// assert!(map.is_ok());
+    ///
+    /// let map = ConcurrentProvenanceMap::<Provenance, i32>::try_new();
+    /// assert!(matches!(map, Err(ProvenanceError::InUse { .. })));
+    /// ```
+    pub fn try_new() -> Result<ConcurrentProvenanceMap<Provenance, Value>, ProvenanceError> {
+        let claim = Claim::acquire::<Provenance>()?;
+
+        Ok(ConcurrentProvenanceMap {
+            buckets: std::array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+            reserved: AtomicUsize::new(0),
+            generation: registry::fresh_generation(),
+            _claim: claim,
+            _pd: Default::default(),
+            _values: Default::default(),
+        })
+    }
+
+    /// Insert a value into this map.
+    /// A unique key is returned. The key may be used to retrieve the value.
+    ///
+    /// Only a shared reference to the map is needed, so references to
+    /// previously inserted values remain usable.
+    /// ```
+    /// use provenance::ConcurrentProvenanceMap;
+    /// struct Provenance;
+    /// let map = ConcurrentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let five = map.get(map.insert(5));
+    /// let six = map.get(map.insert(6));
+    /// assert_eq!(11, five + six);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the map has no room for another value, see
+    /// [try_insert](ConcurrentProvenanceMap::try_insert).
+    pub fn insert(&self, value: Value) -> Key<Provenance> {
+        self.try_insert(value).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Insert a value into this map, unless the map has no room for another value.
+    /// ```
+    /// use provenance::ConcurrentProvenanceMap;
+    /// struct Provenance;
+    /// let map = ConcurrentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.try_insert(5).unwrap();
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    pub fn try_insert(&self, value: Value) -> Result<Key<Provenance>, ProvenanceError> {
+        // Unlike `fetch_add`, this never reserves an index beyond the capacity, nor wraps around.
+        let index = self.reserved
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |reserved| {
+                (reserved < buckets::CAPACITY).then_some(reserved + 1)
+            })
+            .map_err(|_| error::capacity_exceeded::<Provenance>())?;
+        let (bucket, offset) = buckets::locate(index);
+        let entry = &self.bucket(bucket)[offset];
+
+        // The index were reserved above, so no other thread accesses this entry
+        // until `ready` is set.
+        unsafe { (*entry.value.get()).write(value); }
+        entry.ready.store(true, Ordering::Release);
+
+        Ok(Key::new(index, self.generation))
+    }
+
+    /// Use a [key](Key) to retrieve an immutable reference to a stored value.
+    /// ```
+    /// use provenance::ConcurrentProvenanceMap;
+    /// struct Provenance;
+    /// let map = ConcurrentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the key were created by an earlier, since dropped, map with the same provenance.
+    pub fn get(&self, key: Key<Provenance>) -> &Value {
+        assert_eq!(self.generation, key.generation, "{}", FOREIGN_KEY);
+
+        // The key has the correct provenance and generation,
+        // thus we know that we created it in `insert`,
+        // thus its entry is ready.
+        let value = self.value(key.index).expect(FOREIGN_KEY);
+
+        // Values are never mutated through a shared reference to the map.
+        unsafe { &*value }
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to a stored value.
+    /// ```
+    /// use provenance::ConcurrentProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = ConcurrentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// *map.get_mut(key) += 1;
+    /// assert_eq!(&6, map.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the key were created by an earlier, since dropped, map with the same provenance.
+    pub fn get_mut(&mut self, key: Key<Provenance>) -> &mut Value {
+        assert_eq!(self.generation, key.generation, "{}", FOREIGN_KEY);
+        let value = self.value(key.index).expect(FOREIGN_KEY);
+
+        // The map is mutably borrowed, so no other reference to the value exists.
+        unsafe { &mut *value }
+    }
+
+    /// Get an [iterator](Iterator) over all keys in the map.
+    ///
+    /// Values whose insertion is in progress on other threads may or may not be visited.
+    /// ```
+    /// use provenance::ConcurrentProvenanceMap;
+    /// struct Provenance;
+    /// let map = ConcurrentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.insert(3);
+    ///
+    /// assert_eq!(3, map.keys().count());
+    /// ```
+    pub fn keys(&self) -> impl Iterator<Item = Key<Provenance>> + '_ {
+        (0..self.reserved.load(Ordering::Acquire))
+            .filter(move |&index| self.value(index).is_some())
+            .map(move |index| Key::new(index, self.generation))
+    }
+
+    /// Get an [iterator](Iterator) over immutable references to each value in the map.
+    ///
+    /// Values whose insertion is in progress on other threads may or may not be visited.
+    /// ```
+    /// use provenance::ConcurrentProvenanceMap;
+    /// struct Provenance;
+    /// let map = ConcurrentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.insert(3);
+    ///
+    /// assert_eq!(6, map.iter().sum());
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = &Value> {
+        (0..self.reserved.load(Ordering::Acquire))
+            .filter_map(move |index| self.value(index))
+            // Values are never mutated through a shared reference to the map.
+            .map(|value| unsafe { &*value })
+    }
+
+    /// Search the map in insertion order for the first value that satisfy the given predicate.
+    /// If such value is found, an immutable reference to it is returned,
+    /// otherwise [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// use provenance::ConcurrentProvenanceMap;
+    /// struct Provenance;
+    /// let map = ConcurrentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.insert(3);
+    ///
+    /// assert_eq!(Some(&2), map.find(|&val| val == 2));
+    /// assert_eq!(None, map.find(|&val| val == 53));
+    /// ```
+    pub fn find<P: Fn(&Value) -> bool>(&self, predicate: P) -> Option<&Value> {
+        self.iter().find(|value| predicate(value))
+    }
+
+    /// The entries of a bucket, which is allocated if needed.
+    fn bucket(&self, bucket: usize) -> &[Entry<Value>] {
+        let slot = &self.buckets[bucket];
+        let mut entries = slot.load(Ordering::Acquire);
+
+        if entries.is_null() {
+            let allocated = buckets::allocate(bucket, Entry::vacant);
+
+            entries = match slot.compare_exchange(ptr::null_mut(), allocated, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => allocated,
+                Err(current) => {
+                    // Another thread allocated the bucket first.
+                    unsafe { buckets::free(allocated, bucket); }
+                    current
+                }
+            };
+        }
+
+        // Buckets are allocated with their full length, and are only freed when the map is dropped.
+        unsafe { std::slice::from_raw_parts(entries, buckets::bucket_len(bucket)) }
+    }
+
+    /// The value at an index, if one has been inserted there.
+    fn value(&self, index: usize) -> Option<*mut Value> {
+        let (bucket, offset) = buckets::locate(index);
+        let entries = self.buckets[bucket].load(Ordering::Acquire);
+
+        if entries.is_null() {
+            return None;
+        }
+
+        // Buckets are allocated with their full length, and are only freed when the map is dropped.
+        let entry = unsafe { &*entries.add(offset) };
+
+        if entry.ready.load(Ordering::Acquire) {
+            // The value were initialised before `ready` were set.
+            Some(entry.value.get() as *mut Value)
+        } else {
+            None
+        }
+    }
+}
+
+impl<Value> Entry<Value> {
+    fn vacant() -> Entry<Value> {
+        Entry {
+            ready: AtomicBool::new(false),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+}
+
+impl<Provenance, Value> Drop for ConcurrentProvenanceMap<Provenance, Value> {
+    fn drop(&mut self) {
+        for (bucket, slot) in self.buckets.iter_mut().enumerate() {
+            let entries = *slot.get_mut();
+            if entries.is_null() {
+                continue;
+            }
+
+            // The map is dropped, so no insertion is in progress and no reference to a value remains.
+            unsafe {
+                for offset in 0..buckets::bucket_len(bucket) {
+                    let entry = &mut *entries.add(offset);
+                    if *entry.ready.get_mut() {
+                        entry.value.get_mut().assume_init_drop();
+                    }
+                }
+
+                buckets::free(entries, bucket);
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Errors of creating maps and inserting into them.
+
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+/// The reason a map could not be created, see e.g.
+/// [SeparateProvenanceMap::try_new](crate::SeparateProvenanceMap::try_new),
+/// or a value could not be inserted, see e.g.
+/// [SeparateProvenanceMap::try_insert](crate::SeparateProvenanceMap::try_insert).
+///
+/// Every variant names the provenance of the map, as given by [type_name](std::any::type_name).
+/// ```
+/// use provenance::{ProvenanceError, SeparateProvenanceMap};
+///
+/// struct Provenance;
+/// let map = SeparateProvenanceMap::<Provenance, i32>::try_new().unwrap();
+///
+/// let error = SeparateProvenanceMap::<Provenance, i32>::try_new().err().unwrap();
+/// assert!(matches!(error, ProvenanceError::InUse { .. }));
+/// assert!(error.to_string().contains("Provenance"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProvenanceError {
+    /// Another map with the same provenance exists.
+    InUse { provenance: &'static str },
+    /// The map has no room for another value.
+    CapacityExceeded { provenance: &'static str },
+}
+
+impl ProvenanceError {
+
+    /// The name of the provenance of the map that could not be created or inserted into.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    ///
+    /// let map = ProvenanceMap::<u8>::try_new().unwrap();
+    /// let error = ProvenanceMap::<u8>::try_new().err().unwrap();
+    /// assert_eq!("u8", error.provenance());
+    /// ```
+    pub fn provenance(&self) -> &'static str {
+        match self {
+            ProvenanceError::InUse { provenance } => provenance,
+            ProvenanceError::CapacityExceeded { provenance } => provenance,
+        }
+    }
+}
+
+/// The error of inserting into a full map with some provenance.
+pub(crate) fn capacity_exceeded<Provenance>() -> ProvenanceError {
+    ProvenanceError::CapacityExceeded { provenance: std::any::type_name::<Provenance>() }
+}
+
+impl Display for ProvenanceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProvenanceError::InUse { provenance } =>
+                write!(f, "provenance `{}` is already used by another map", provenance),
+            ProvenanceError::CapacityExceeded { provenance } =>
+                write!(f, "capacity of map with provenance `{}` exceeded", provenance),
+        }
+    }
+}
+
+impl Error for ProvenanceError {}
@@ -0,0 +1,110 @@
+//! A map that may be declared in a `static`.
+
+use std::{
+    ops::Deref,
+    sync::{Mutex, OnceLock, PoisonError},
+};
+use crate::{ConcurrentProvenanceMap, ProvenanceError};
+
+/// A [ConcurrentProvenanceMap](ConcurrentProvenanceMap) that may be declared in a `static`.
+///
+/// The map claims its provenance when it first is used, and dereferences to the
+/// underlying [ConcurrentProvenanceMap](ConcurrentProvenanceMap). Since a `static`
+/// never is dropped, neither is the map, so its keys stay valid for the rest of the program.
+/// ```
+/// use provenance::{GlobalProvenanceMap, Key};
+///
+/// struct Currencies;
+/// static CURRENCIES: GlobalProvenanceMap<Currencies, &str> = GlobalProvenanceMap::new();
+///
+/// let sek: Key<Currencies> = CURRENCIES.insert("SEK");
+/// let name = std::thread::spawn(move || *CURRENCIES.get(sek)).join().unwrap();
+/// assert_eq!("SEK", name);
+/// ```
+///
+/// The provenance is claimed like that of any other map, so the global map can not
+/// be used while a map with the same provenance exists, and vice versa.
+/// ```
+/// use provenance::{GlobalProvenanceMap, ProvenanceError, SeparateProvenanceMap};
+///
+/// struct Provenance;
+/// static MAP: GlobalProvenanceMap<Provenance, i32> = GlobalProvenanceMap::new();
+///
+/// let local = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+/// assert!(matches!(MAP.try_force(), Err(ProvenanceError::InUse { .. })));
+///
+/// drop(local);
+/// MAP.insert(5);
+/// assert!(SeparateProvenanceMap::<Provenance, i32>::new().is_none());
+/// ```
+pub struct GlobalProvenanceMap<Provenance, Value> {
+    map: OnceLock<ConcurrentProvenanceMap<Provenance, Value>>,
+    /// Held while creating the map, so that threads racing to create it do not
+    /// see each other's claim as a conflicting map.
+    creating: Mutex<()>,
+}
+
+impl<Provenance: 'static, Value: 'static> GlobalProvenanceMap<Provenance, Value> {
+
+    /// Declare a map, without claiming its provenance.
+    /// ```
+    /// use provenance::GlobalProvenanceMap;
+    ///
+    /// struct Provenance;
+    /// static MAP: GlobalProvenanceMap<Provenance, i32> = GlobalProvenanceMap::new();
+    /// ```
+    pub const fn new() -> GlobalProvenanceMap<Provenance, Value> {
+        GlobalProvenanceMap {
+            map: OnceLock::new(),
+            creating: Mutex::new(()),
+        }
+    }
+
+    /// Get the underlying map, creating it if this is its first use.
+    ///
+    /// If the map could not be created, an [error](ProvenanceError) describing why is
+    /// returned, and creation is attempted again next time the map is used.
+    /// ```
+    /// use provenance::GlobalProvenanceMap;
+    ///
+    /// struct Provenance;
+    /// static MAP: GlobalProvenanceMap<Provenance, i32> = GlobalProvenanceMap::new();
+    ///
+    /// let map = MAP.try_force().unwrap();
+    /// let key = map.insert(5);
+    /// assert_eq!(&5, MAP.get(key));
+    /// ```
+    pub fn try_force(&self) -> Result<&ConcurrentProvenanceMap<Provenance, Value>, ProvenanceError> {
+        if let Some(map) = self.map.get() {
+            return Ok(map);
+        }
+
+        // Nothing guarded by the lock may be left inconsistent, so a poisoned lock is safe to use.
+        let _creating = self.creating.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(map) = self.map.get() {
+            return Ok(map);
+        }
+
+        let map = ConcurrentProvenanceMap::try_new()?;
+        Ok(self.map.get_or_init(|| map))
+    }
+}
+
+impl<Provenance: 'static, Value: 'static> Default for GlobalProvenanceMap<Provenance, Value> {
+    fn default() -> Self {
+        GlobalProvenanceMap::new()
+    }
+}
+
+impl<Provenance: 'static, Value: 'static> Deref for GlobalProvenanceMap<Provenance, Value> {
+    type Target = ConcurrentProvenanceMap<Provenance, Value>;
+
+    /// Get the underlying map, creating it if this is its first use.
+    ///
+    /// # Panics
+    /// Panics if the map could not be created, see [try_force](GlobalProvenanceMap::try_force).
+    fn deref(&self) -> &Self::Target {
+        self.try_force().unwrap_or_else(|error| panic!("{}", error))
+    }
+}
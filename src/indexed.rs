@@ -0,0 +1,740 @@
+//! A map with secondary indices over its values.
+
+use std::{
+    any::Any,
+    borrow::Borrow,
+    collections::HashMap,
+    error::Error,
+    fmt::{Debug, Display, Formatter},
+    hash::Hash,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+use crate::{Key, OwnedKey, ProvenanceError, SeparateProvenanceMap};
+
+const FOREIGN_INDEX: &str = "index were declared on another map with the same provenance";
+const CONFLICTING_MODIFICATION: &str = "modified value conflicts with another value in a unique index";
+
+/// A [SeparateProvenanceMap](SeparateProvenanceMap) which keeps indices of keys derived
+/// from its values, such that values may be looked up without searching the map.
+///
+/// Indices are declared with [unique_index_by](IndexedProvenanceMap::unique_index_by)
+/// and [index_by](IndexedProvenanceMap::index_by), which return typed handles used to
+/// look values up. Every index is updated as values are inserted, [modified](IndexedProvenanceMap::get_mut)
+/// and removed.
+///
+/// Values may also be read through the underlying [SeparateProvenanceMap](SeparateProvenanceMap)
+/// the map dereferences to. It can not be dereferenced mutably, as a value changed behind
+/// the back of the indices would leave them stale.
+/// ```
+/// use provenance::IndexedProvenanceMap;
+///
+/// #[derive(Debug)]
+/// struct Currency {
+///     name: String,
+///     region: &'static str,
+/// }
+///
+/// struct Currencies;
+/// let mut currencies = IndexedProvenanceMap::<Currencies, Currency>::new().unwrap();
+/// let by_name = currencies.unique_index_by(|currency| currency.name.clone()).unwrap();
+/// let by_region = currencies.index_by(|currency| currency.region);
+///
+/// let sek = currencies.insert(Currency { name: "SEK".to_string(), region: "Nordic" }).unwrap();
+/// let nok = currencies.insert(Currency { name: "NOK".to_string(), region: "Nordic" }).unwrap();
+///
+/// assert_eq!(Some(sek), currencies.lookup_unique(&by_name, "SEK"));
+/// assert_eq!(vec![sek, nok], currencies.lookup_all(&by_region, &"Nordic").collect::<Vec<_>>());
+///
+/// // Names are unique
+/// assert!(currencies.insert(Currency { name: "SEK".to_string(), region: "Sweden" }).is_err());
+/// ```
+pub struct IndexedProvenanceMap<Provenance, Value> {
+    map: SeparateProvenanceMap<Provenance, Value>,
+    indices: Vec<Box<dyn Indexing<Provenance, Value>>>,
+}
+
+/// A handle to an index of an [IndexedProvenanceMap](IndexedProvenanceMap), where each
+/// index key belongs to at most one value.
+///
+/// Created with [unique_index_by](IndexedProvenanceMap::unique_index_by).
+pub struct UniqueIndex<Provenance, IndexKey> {
+    position: usize,
+    instance: u64,
+    _pd: PhantomData<fn() -> (Provenance, IndexKey)>,
+}
+
+/// A handle to an index of an [IndexedProvenanceMap](IndexedProvenanceMap), where each
+/// index key may belong to any number of values.
+///
+/// Created with [index_by](IndexedProvenanceMap::index_by).
+pub struct MultiIndex<Provenance, IndexKey> {
+    position: usize,
+    instance: u64,
+    _pd: PhantomData<fn() -> (Provenance, IndexKey)>,
+}
+
+/// The error of inserting or modifying a value of an [IndexedProvenanceMap](IndexedProvenanceMap),
+/// such that it would have the same key as another value in a unique index.
+///
+/// The value that were rejected is given back with [into_value](IndexConflict::into_value).
+/// For a [committed](IndexedMut::commit) modification, that is the reference to the
+/// modified value, so it may be changed and committed again.
+pub struct IndexConflict<Provenance, Value> {
+    existing: Key<Provenance>,
+    value: Value,
+}
+
+/// A mutable reference to a value of an [IndexedProvenanceMap](IndexedProvenanceMap).
+///
+/// The value is left out of every index while it is modified, and is re-indexed
+/// once the modification is [committed](IndexedMut::commit). Dropping the reference
+/// re-indexes the value too, but then panics if the value conflicts with another
+/// value in a unique index, see [get_mut](IndexedProvenanceMap::get_mut).
+pub struct IndexedMut<'a, Provenance, Value> {
+    key: Key<Provenance>,
+    value: &'a mut Value,
+    indices: &'a mut Vec<Box<dyn Indexing<Provenance, Value>>>,
+    committed: bool,
+}
+
+/// The error of declaring a unique index on an [IndexedProvenanceMap](IndexedProvenanceMap),
+/// whose values already have the same index key.
+pub struct DuplicateIndexKey<Provenance> {
+    first: Key<Provenance>,
+    second: Key<Provenance>,
+}
+
+/// An index of some type of index key, with the type erased such that indices of
+/// different types may be kept together.
+trait Indexing<Provenance, Value> {
+    /// The key of another value than the one of `key` with the same index key,
+    /// if the index is unique.
+    fn conflict(&self, key: Option<Key<Provenance>>, value: &Value) -> Option<Key<Provenance>>;
+    fn insert(&mut self, key: Key<Provenance>, value: &Value);
+    fn remove(&mut self, key: Key<Provenance>, value: &Value);
+    fn as_any(&self) -> &dyn Any;
+}
+
+struct Table<Provenance, Value, IndexKey> {
+    unique: bool,
+    extract: Box<dyn Fn(&Value) -> IndexKey>,
+    /// Keys of the values of each index key, in the order they were indexed.
+    entries: HashMap<IndexKey, Vec<Key<Provenance>>>,
+}
+
+impl<Provenance: 'static, Value: 'static> IndexedProvenanceMap<Provenance, Value> {
+
+    /// Creates a new empty map with some type as provenance, without any indices.
+    ///
+    /// The provenance is claimed like by [SeparateProvenanceMap::new](SeparateProvenanceMap::new),
+    /// so [`None`](std::option::Option::None) is returned if any map with such provenance exists.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    ///
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// map.insert(1).unwrap();
+    /// map.insert(1).unwrap(); // No unique index is declared yet
+    ///
+    /// assert!(IndexedProvenanceMap::<Provenance, i32>::new().is_none());
+    /// ```
+    pub fn new() -> Option<IndexedProvenanceMap<Provenance, Value>> {
+        IndexedProvenanceMap::try_new().ok()
+    }
+
+    /// Creates a new empty map with some type as provenance, without any indices,
+    /// or an [error](ProvenanceError) of why it could not be created.
+    /// See [SeparateProvenanceMap::try_new](SeparateProvenanceMap::try_new).
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    ///
+    /// let map = IndexedProvenanceMap::<String, String>::try_new().unwrap();
+    /// let error = IndexedProvenanceMap::<String, String>::try_new().err().unwrap();
+    /// assert_eq!("alloc::string::String", error.provenance());
+    /// ```
+    pub fn try_new() -> Result<IndexedProvenanceMap<Provenance, Value>, ProvenanceError> {
+        Ok(IndexedProvenanceMap {
+            map: SeparateProvenanceMap::try_new()?,
+            indices: vec![],
+        })
+    }
+
+    /// Declare an index of keys derived from the values, where each index key belongs
+    /// to at most one value. Values already in the map are indexed.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, (u32, &str)>::new().unwrap();
+    ///
+    /// let key = map.insert((7, "seven")).unwrap();
+    /// let by_number = map.unique_index_by(|&(number, _)| number).unwrap();
+    ///
+    /// assert_eq!(Some(key), map.lookup_unique(&by_number, &7));
+    /// ```
+    ///
+    /// If values already in the map have the same index key, the index is not declared,
+    /// and an [error](DuplicateIndexKey) with the keys of two such values is returned.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let minus_one = map.insert(-1).unwrap();
+    /// let one = map.insert(1).unwrap();
+    ///
+    /// let duplicate = map.unique_index_by(|val| val.abs()).err().unwrap();
+    /// assert_eq!((minus_one, one), duplicate.keys());
+    /// ```
+    pub fn unique_index_by<K, F>(&mut self, extract: F) -> Result<UniqueIndex<Provenance, K>, DuplicateIndexKey<Provenance>>
+        where K: Hash + Eq + 'static, F: Fn(&Value) -> K + 'static
+    {
+        Ok(UniqueIndex {
+            position: self.declare(true, Box::new(extract))?,
+            instance: self.map.instance,
+            _pd: Default::default(),
+        })
+    }
+
+    /// Declare an index of keys derived from the values, where each index key may
+    /// belong to any number of values. Values already in the map are indexed.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1).unwrap();
+    /// map.insert(2).unwrap();
+    /// let three = map.insert(3).unwrap();
+    /// let by_parity = map.index_by(|val| val % 2);
+    ///
+    /// assert_eq!(vec![one, three], map.lookup_all(&by_parity, &1).collect::<Vec<_>>());
+    /// ```
+    pub fn index_by<K, F>(&mut self, extract: F) -> MultiIndex<Provenance, K>
+        where K: Hash + Eq + 'static, F: Fn(&Value) -> K + 'static
+    {
+        // Only unique indices may have duplicates.
+        let position = self.declare(false, Box::new(extract)).expect("index is not unique");
+
+        MultiIndex {
+            position,
+            instance: self.map.instance,
+            _pd: Default::default(),
+        }
+    }
+
+    /// Look up the key of the value with some index key in a unique index.
+    ///
+    /// The index key may be given in any form the index keys may be [borrowed](Borrow) as.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, String>::new().unwrap();
+    /// let by_name = map.unique_index_by(|name| name.to_lowercase()).unwrap();
+    ///
+    /// let key = map.insert("SEK".to_string()).unwrap();
+    /// assert_eq!(Some(key), map.lookup_unique(&by_name, "sek"));
+    /// assert_eq!(None, map.lookup_unique(&by_name, "nok"));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the index were declared on an earlier, since dropped, map with the same provenance.
+    /// ```should_panic
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    ///
+    /// let mut old = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// let by_value = old.unique_index_by(|&val| val).unwrap();
+    /// drop(old);
+    ///
+    /// let mut new = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// new.unique_index_by(|&val| val).unwrap();
+    /// new.lookup_unique(&by_value, &5); // Panics since the index is of the old map
+    /// ```
+    pub fn lookup_unique<K, Q>(&self, index: &UniqueIndex<Provenance, K>, index_key: &Q) -> Option<Key<Provenance>>
+        where K: Borrow<Q> + Hash + Eq + 'static, Q: Hash + Eq + ?Sized
+    {
+        self.table::<K>(index.position, index.instance, true).entries.get(index_key)?.first().copied()
+    }
+
+    /// Look up the keys of every value with some index key, in the order they were indexed.
+    ///
+    /// The index key may be given in any form the index keys may be [borrowed](Borrow) as.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, (&str, i32)>::new().unwrap();
+    /// let by_owner = map.index_by(|&(owner, _)| owner.to_string());
+    ///
+    /// map.insert(("alice", 10)).unwrap();
+    /// map.insert(("bob", 20)).unwrap();
+    /// map.insert(("alice", 30)).unwrap();
+    ///
+    /// let balance: i32 = map.lookup_all(&by_owner, "alice").map(|key| map.get(key).1).sum();
+    /// assert_eq!(40, balance);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the index were declared on an earlier, since dropped, map with the same provenance.
+    pub fn lookup_all<K, Q>(&self, index: &MultiIndex<Provenance, K>, index_key: &Q) -> impl Iterator<Item = Key<Provenance>> + '_
+        where K: Borrow<Q> + Hash + Eq + 'static, Q: Hash + Eq + ?Sized
+    {
+        self.table::<K>(index.position, index.instance, false).entries.get(index_key)
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    /// Insert a value into this map, indexing it.
+    ///
+    /// If the value has the same key as another value in a unique index, the value is
+    /// not inserted, and an [error](IndexConflict) giving it back is returned.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// map.unique_index_by(|&val| val).unwrap();
+    ///
+    /// let key = map.insert(5).unwrap();
+    ///
+    /// let conflict = map.insert(5).err().unwrap();
+    /// assert_eq!(key, conflict.existing());
+    /// assert_eq!(5, conflict.into_value());
+    /// ```
+    pub fn insert(&mut self, value: Value) -> Result<Key<Provenance>, IndexConflict<Provenance, Value>> {
+        if let Some(existing) = self.conflict(&value) {
+            return Err(IndexConflict { existing, value });
+        }
+        let key = self.map.insert(value);
+        self.index(key);
+        Ok(key)
+    }
+
+    /// Insert a value into this map, indexing it, and get an [owned key](OwnedKey) to it.
+    ///
+    /// If the value has the same key as another value in a unique index, the value is
+    /// not inserted, and an [error](IndexConflict) giving it back is returned.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// let by_value = map.unique_index_by(|&val| val).unwrap();
+    ///
+    /// let owned = map.insert_owned(5).ok().unwrap();
+    /// assert_eq!(Some(&5), map.lookup_unique(&by_value, &5).map(|key| map.get(key)));
+    /// ```
+    pub fn insert_owned(&mut self, value: Value) -> Result<OwnedKey<Provenance>, IndexConflict<Provenance, Value>> {
+        if let Some(existing) = self.conflict(&value) {
+            return Err(IndexConflict { existing, value });
+        }
+        let owned = self.map.insert_owned(value);
+        self.index(owned.key);
+        Ok(owned)
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to a stored value, which
+    /// re-indexes the value once the modification is [committed](IndexedMut::commit).
+    ///
+    /// If the modified value has the same key as another value in a unique index,
+    /// committing it fails with an [error](IndexConflict) giving back the reference.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, String>::new().unwrap();
+    /// let by_name = map.unique_index_by(|name| name.clone()).unwrap();
+    ///
+    /// let sek = map.insert("SEK".to_string()).unwrap();
+    /// let mut name = map.get_mut(sek);
+    /// *name = "NOK".to_string();
+    /// name.commit().unwrap();
+    /// assert_eq!(None, map.lookup_unique(&by_name, "SEK"));
+    /// assert_eq!(Some(sek), map.lookup_unique(&by_name, "NOK"));
+    ///
+    /// let dkk = map.insert("DKK".to_string()).unwrap();
+    /// let mut name = map.get_mut(dkk);
+    /// *name = "NOK".to_string();
+    /// let conflict = name.commit().err().unwrap();
+    /// assert_eq!(sek, conflict.existing());
+    ///
+    /// // Change the value back, and commit it again
+    /// let mut name = conflict.into_value();
+    /// *name = "DKK".to_string();
+    /// name.commit().unwrap();
+    /// assert_eq!(Some(dkk), map.lookup_unique(&by_name, "DKK"));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value of the key has been removed, see [SeparateProvenanceMap::get_mut].
+    ///
+    /// The returned reference may also be dropped without being committed, which
+    /// re-indexes the value just the same. As a conflict then can not be returned,
+    /// the reference panics when dropped if the value has the same key as another
+    /// value in a unique index. The value is still indexed, such that the indices stay
+    /// consistent with the map, but the index key is no longer unique.
+    /// ```should_panic
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// map.unique_index_by(|&val| val).unwrap();
+    ///
+    /// map.insert(1).unwrap();
+    /// let two = map.insert(2).unwrap();
+    /// *map.get_mut(two) = 1; // Panics since 1 already is in the index
+    /// ```
+    pub fn get_mut(&mut self, key: Key<Provenance>) -> IndexedMut<'_, Provenance, Value> {
+        IndexedMut::new(key, self.map.get_mut(key), &mut self.indices)
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to a stored value, if the
+    /// value still is in the map.
+    ///
+    /// The value is re-indexed like by [get_mut](IndexedProvenanceMap::get_mut).
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5).unwrap();
+    /// map.remove(key);
+    ///
+    /// assert!(map.try_get_mut(key).is_none());
+    /// ```
+    pub fn try_get_mut(&mut self, key: Key<Provenance>) -> Option<IndexedMut<'_, Provenance, Value>> {
+        Some(IndexedMut::new(key, self.map.try_get_mut(key)?, &mut self.indices))
+    }
+
+    /// Use an [owned key](OwnedKey) to retrieve a mutable reference to its value.
+    ///
+    /// The value is re-indexed like by [get_mut](IndexedProvenanceMap::get_mut).
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// let by_value = map.unique_index_by(|&val| val).unwrap();
+    ///
+    /// let owned = map.insert_owned(5).ok().unwrap();
+    /// let mut val = map.get_owned_mut(&owned);
+    /// *val += 1;
+    /// val.commit().unwrap();
+    /// assert!(map.lookup_unique(&by_value, &6).is_some());
+    /// ```
+    ///
+    /// # Panics
+    /// Panics like [SeparateProvenanceMap::get_owned_mut].
+    pub fn get_owned_mut(&mut self, key: &OwnedKey<Provenance>) -> IndexedMut<'_, Provenance, Value> {
+        IndexedMut::new(key.key, self.map.get_owned_mut(key), &mut self.indices)
+    }
+
+    /// Remove a value from the map, and from every index.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// let by_value = map.unique_index_by(|&val| val).unwrap();
+    ///
+    /// let key = map.insert(5).unwrap();
+    /// assert_eq!(5, map.remove(key));
+    /// assert_eq!(None, map.lookup_unique(&by_value, &5));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics like [SeparateProvenanceMap::remove].
+    pub fn remove(&mut self, key: Key<Provenance>) -> Value {
+        let value = self.map.remove(key);
+        self.indices.iter_mut().for_each(|index| index.remove(key, &value));
+        value
+    }
+
+    /// Remove a value inserted with [insert_owned](IndexedProvenanceMap::insert_owned),
+    /// and from every index.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5).ok().unwrap();
+    /// assert_eq!(5, map.remove_owned(owned));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics like [SeparateProvenanceMap::remove_owned].
+    pub fn remove_owned(&mut self, key: OwnedKey<Provenance>) -> Value {
+        let removed = key.key;
+        let value = self.map.remove_owned(key);
+        self.indices.iter_mut().for_each(|index| index.remove(removed, &value));
+        value
+    }
+
+    fn declare<K>(&mut self, unique: bool, extract: Box<dyn Fn(&Value) -> K>) -> Result<usize, DuplicateIndexKey<Provenance>>
+        where K: Hash + Eq + 'static
+    {
+        let mut table = Table { unique, extract, entries: HashMap::new() };
+        for (key, value) in self.map.entries() {
+            if let Some(first) = table.conflict(Some(key), value) {
+                return Err(DuplicateIndexKey { first, second: key });
+            }
+            table.insert(key, value);
+        }
+
+        self.indices.push(Box::new(table));
+        Ok(self.indices.len() - 1)
+    }
+
+    /// The table of a handle, which must have been declared on this very map, with
+    /// the same uniqueness.
+    fn table<K: 'static>(&self, position: usize, instance: u64, unique: bool) -> &Table<Provenance, Value, K> {
+        Some(position)
+            .filter(|_| instance == self.map.instance)
+            .and_then(|position| self.indices.get(position))
+            .and_then(|index| index.as_any().downcast_ref::<Table<Provenance, Value, K>>())
+            .filter(|table| table.unique == unique)
+            .expect(FOREIGN_INDEX)
+    }
+
+    fn conflict(&self, value: &Value) -> Option<Key<Provenance>> {
+        self.indices.iter().find_map(|index| index.conflict(None, value))
+    }
+
+    fn index(&mut self, key: Key<Provenance>) {
+        let value = self.map.get(key);
+        self.indices.iter_mut().for_each(|index| index.insert(key, value));
+    }
+}
+
+impl<Provenance: 'static, Value: 'static, IndexKey: Hash + Eq + 'static> Indexing<Provenance, Value>
+    for Table<Provenance, Value, IndexKey>
+{
+    fn conflict(&self, key: Option<Key<Provenance>>, value: &Value) -> Option<Key<Provenance>> {
+        if !self.unique {
+            return None;
+        }
+
+        self.entries.get(&(self.extract)(value))?
+            .iter()
+            .copied()
+            .find(|&indexed| Some(indexed) != key)
+    }
+
+    fn insert(&mut self, key: Key<Provenance>, value: &Value) {
+        self.entries.entry((self.extract)(value)).or_default().push(key);
+    }
+
+    fn remove(&mut self, key: Key<Provenance>, value: &Value) {
+        let index_key = (self.extract)(value);
+        if let Some(keys) = self.entries.get_mut(&index_key) {
+            keys.retain(|&indexed| indexed != key);
+            if keys.is_empty() {
+                self.entries.remove(&index_key);
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<'a, Provenance, Value> IndexedMut<'a, Provenance, Value> {
+
+    /// Remove the value from every index, until the modification is committed.
+    fn new(
+        key: Key<Provenance>,
+        value: &'a mut Value,
+        indices: &'a mut Vec<Box<dyn Indexing<Provenance, Value>>>,
+    ) -> IndexedMut<'a, Provenance, Value> {
+        indices.iter_mut().for_each(|index| index.remove(key, value));
+        IndexedMut { key, value, indices, committed: false }
+    }
+
+    /// Re-index the modified value, unless it has the same key as another value in
+    /// a unique index. In that case the value is left unindexed, and an [error](IndexConflict)
+    /// giving back this reference is returned. Dropping the error drops the reference,
+    /// which then panics like any reference dropped with a conflicting value.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// let by_value = map.unique_index_by(|&val| val).unwrap();
+    ///
+    /// let one = map.insert(1).unwrap();
+    /// let two = map.insert(2).unwrap();
+    ///
+    /// let mut val = map.get_mut(two);
+    /// *val = 1;
+    /// let mut val = val.commit().err().unwrap().into_value();
+    /// *val = 3;
+    /// val.commit().unwrap();
+    ///
+    /// assert_eq!(Some(one), map.lookup_unique(&by_value, &1));
+    /// assert_eq!(Some(two), map.lookup_unique(&by_value, &3));
+    /// ```
+    pub fn commit(mut self) -> Result<(), IndexConflict<Provenance, IndexedMut<'a, Provenance, Value>>> {
+        if let Some(existing) = self.indices.iter().find_map(|index| index.conflict(Some(self.key), self.value)) {
+            return Err(IndexConflict { existing, value: self });
+        }
+
+        for index in self.indices.iter_mut() {
+            index.insert(self.key, self.value);
+        }
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl<Provenance, Value> IndexConflict<Provenance, Value> {
+
+    /// The key of the value in the map with the same index key.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// map.unique_index_by(|&val| val).unwrap();
+    ///
+    /// let key = map.insert(5).unwrap();
+    /// assert_eq!(key, map.insert(5).err().unwrap().existing());
+    /// ```
+    pub fn existing(&self) -> Key<Provenance> {
+        self.existing
+    }
+
+    /// Take back the value that could not be inserted.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, String>::new().unwrap();
+    /// map.unique_index_by(|name| name.to_lowercase()).unwrap();
+    ///
+    /// map.insert("SEK".to_string()).unwrap();
+    /// assert_eq!("sek", map.insert("sek".to_string()).err().unwrap().into_value());
+    /// ```
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+}
+
+impl<Provenance, Value> Display for IndexConflict<Provenance, Value> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "value conflicts with the value of {:?} in a unique index", self.existing)
+    }
+}
+
+impl<Provenance, Value: Debug> Debug for IndexConflict<Provenance, Value> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IndexConflict")
+            .field("existing", &self.existing)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+impl<Provenance, Value: Debug> Error for IndexConflict<Provenance, Value> {}
+
+impl<Provenance> DuplicateIndexKey<Provenance> {
+
+    /// The keys of two values in the map with the same index key, in the order
+    /// they are in the map.
+    /// ```
+    /// use provenance::IndexedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = IndexedProvenanceMap::<Provenance, &str>::new().unwrap();
+    ///
+    /// let sek = map.insert("SEK").unwrap();
+    /// let nok = map.insert("NOK").unwrap();
+    /// map.insert("DKK").unwrap();
+    ///
+    /// let duplicate = map.unique_index_by(|name| name.ends_with('K')).err().unwrap();
+    /// assert_eq!((sek, nok), duplicate.keys());
+    /// ```
+    pub fn keys(&self) -> (Key<Provenance>, Key<Provenance>) {
+        (self.first, self.second)
+    }
+}
+
+impl<Provenance> Display for DuplicateIndexKey<Provenance> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "values of {:?} and {:?} have the same key in a unique index", self.first, self.second)
+    }
+}
+
+impl<Provenance> Debug for DuplicateIndexKey<Provenance> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DuplicateIndexKey")
+            .field("first", &self.first)
+            .field("second", &self.second)
+            .finish()
+    }
+}
+
+impl<Provenance> Error for DuplicateIndexKey<Provenance> {}
+
+impl<'a, Provenance, Value> Deref for IndexedMut<'a, Provenance, Value> {
+    type Target = Value;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<'a, Provenance, Value> DerefMut for IndexedMut<'a, Provenance, Value> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+impl<'a, Provenance, Value> Drop for IndexedMut<'a, Provenance, Value> {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+
+        let mut conflicts = false;
+        for index in self.indices.iter_mut() {
+            conflicts |= index.conflict(Some(self.key), self.value).is_some();
+            index.insert(self.key, self.value);
+        }
+
+        // Panicking while already unwinding would abort.
+        if conflicts && !std::thread::panicking() {
+            panic!("{}", CONFLICTING_MODIFICATION);
+        }
+    }
+}
+
+impl<Provenance, Value> Deref for IndexedProvenanceMap<Provenance, Value> {
+    type Target = SeparateProvenanceMap<Provenance, Value>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<Provenance: 'static, Value: Debug + 'static> Debug for IndexedProvenanceMap<Provenance, Value> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+impl<'a, Provenance, Value: Debug> Debug for IndexedMut<'a, Provenance, Value> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+// Clone + Copy
+// Implemented manually, as deriving would require the provenance and index key to implement them too.
+
+impl<Provenance, IndexKey> Clone for UniqueIndex<Provenance, IndexKey> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Provenance, IndexKey> Copy for UniqueIndex<Provenance, IndexKey> {}
+
+impl<Provenance, IndexKey> Clone for MultiIndex<Provenance, IndexKey> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Provenance, IndexKey> Copy for MultiIndex<Provenance, IndexKey> {}
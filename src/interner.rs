@@ -0,0 +1,194 @@
+//! A map that stores each distinct value once.
+
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+};
+use crate::{Key, ProvenanceError, SeparateProvenanceMap};
+
+/// A [SeparateProvenanceMap](SeparateProvenanceMap) where equal values share a key.
+///
+/// Interning a value that is equal to one already in the interner returns the key
+/// of that value, instead of inserting it again. Values are never removed or changed,
+/// so a key can always be resolved to its value.
+/// ```
+/// use provenance::ProvenanceInterner;
+///
+/// struct Symbols;
+/// let mut symbols = ProvenanceInterner::<Symbols, String>::new().unwrap();
+///
+/// let sek = symbols.intern("SEK".to_string());
+/// let nok = symbols.intern("NOK".to_string());
+///
+/// assert_eq!(sek, symbols.intern("SEK".to_string()));
+/// assert_ne!(sek, nok);
+/// assert_eq!("SEK", symbols.resolve(sek));
+/// ```
+pub struct ProvenanceInterner<Provenance, Value> {
+    map: SeparateProvenanceMap<Provenance, Value>,
+    /// Keys of the values in the map, by the hash of their value.
+    index: HashMap<u64, Vec<Key<Provenance>>>,
+    hasher: RandomState,
+}
+
+impl<Provenance: 'static, Value: Hash + Eq + 'static> ProvenanceInterner<Provenance, Value> {
+
+    /// Creates a new empty interner with some type as provenance.
+    ///
+    /// If a map with such provenance already exists, [`None`](std::option::Option::None) will be returned.
+    /// ```
+    /// use provenance::ProvenanceInterner;
+    /// struct Provenance;
+    ///
+    /// let interner = ProvenanceInterner::<Provenance, String>::new();
+    /// assert!(interner.is_some());
+    ///
+    /// let interner = ProvenanceInterner::<Provenance, String>::new();
+    /// assert!(interner.is_none());
+    /// ```
+    pub fn new() -> Option<ProvenanceInterner<Provenance, Value>> {
+        ProvenanceInterner::try_new().ok()
+    }
+
+    /// Creates a new empty interner with some type as provenance.
+    ///
+    /// If a map with such provenance already exists, or the interner could not be created
+    /// for another reason, an [error](ProvenanceError) describing why is returned.
+    /// ```
+    /// use provenance::{ProvenanceError, ProvenanceInterner};
+    /// struct Provenance;
+    ///
+    /// let interner = ProvenanceInterner::<Provenance, String>::try_new();
+    /// assert!(interner.is_ok());
+    ///
+    /// let interner = ProvenanceInterner::<Provenance, String>::try_new();
+    /// assert!(matches!(interner, Err(ProvenanceError::InUse { .. })));
+    /// ```
+    pub fn try_new() -> Result<ProvenanceInterner<Provenance, Value>, ProvenanceError> {
+        Ok(ProvenanceInterner {
+            map: SeparateProvenanceMap::try_new()?,
+            index: HashMap::new(),
+            hasher: RandomState::new(),
+        })
+    }
+
+    /// Intern a value, returning its key.
+    ///
+    /// If an equal value already is interned, its key is returned and the given value is dropped.
+    /// ```
+    /// use provenance::ProvenanceInterner;
+    /// struct Provenance;
+    /// let mut interner = ProvenanceInterner::<Provenance, i32>::new().unwrap();
+    ///
+    /// let five = interner.intern(5);
+    /// assert_eq!(five, interner.intern(5));
+    /// assert_eq!(1, interner.keys().count());
+    /// ```
+    pub fn intern(&mut self, value: Value) -> Key<Provenance> {
+        match self.key_of(&value) {
+            Some(key) => key,
+            None => self.insert(value),
+        }
+    }
+
+    /// Find the key of an interned value equal to the given one, without interning it.
+    ///
+    /// The value may be given in any form the interned values may be [borrowed](Borrow) as.
+    /// ```
+    /// use provenance::ProvenanceInterner;
+    /// struct Provenance;
+    /// let mut interner = ProvenanceInterner::<Provenance, String>::new().unwrap();
+    ///
+    /// let sek = interner.intern("SEK".to_string());
+    /// assert_eq!(Some(sek), interner.key_of("SEK"));
+    /// assert_eq!(None, interner.key_of("NOK"));
+    /// ```
+    pub fn key_of<Q>(&self, value: &Q) -> Option<Key<Provenance>>
+        where Value: Borrow<Q>, Q: Hash + Eq + ?Sized
+    {
+        self.index.get(&self.hasher.hash_one(value))?
+            .iter()
+            .copied()
+            .find(|&key| self.map.get(key).borrow() == value)
+    }
+
+    /// Use a [key](Key) to retrieve an immutable reference to an interned value.
+    /// ```
+    /// use provenance::ProvenanceInterner;
+    /// struct Provenance;
+    /// let mut interner = ProvenanceInterner::<Provenance, String>::new().unwrap();
+    ///
+    /// let key = interner.intern("SEK".to_string());
+    /// assert_eq!("SEK", interner.resolve(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the key were created by an earlier, since dropped, map with the same provenance.
+    pub fn resolve(&self, key: Key<Provenance>) -> &Value {
+        self.map.get(key)
+    }
+
+    /// Get an [iterator](Iterator) over all keys in the interner.
+    /// ```
+    /// use provenance::ProvenanceInterner;
+    /// struct Provenance;
+    /// let mut interner = ProvenanceInterner::<Provenance, i32>::new().unwrap();
+    ///
+    /// interner.intern(1);
+    /// interner.intern(2);
+    /// interner.intern(1);
+    ///
+    /// assert_eq!(2, interner.keys().count());
+    /// ```
+    pub fn keys(&self) -> impl Iterator<Item = Key<Provenance>> + '_ {
+        self.map.keys()
+    }
+
+    /// Get an [iterator](Iterator) over immutable references to each value in the interner.
+    /// ```
+    /// use provenance::ProvenanceInterner;
+    /// struct Provenance;
+    /// let mut interner = ProvenanceInterner::<Provenance, i32>::new().unwrap();
+    ///
+    /// interner.intern(1);
+    /// interner.intern(2);
+    /// interner.intern(1);
+    ///
+    /// assert_eq!(3, interner.iter().sum());
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = &Value> {
+        self.map.iter()
+    }
+
+    /// Insert a value that is not already interned.
+    fn insert(&mut self, value: Value) -> Key<Provenance> {
+        let hash = self.hasher.hash_one(&value);
+        let key = self.map.insert(value);
+        self.index.entry(hash).or_default().push(key);
+
+        key
+    }
+}
+
+impl<Provenance: 'static> ProvenanceInterner<Provenance, String> {
+
+    /// Intern a string, returning its key.
+    ///
+    /// The string is only copied if no equal string already is interned.
+    /// ```
+    /// use provenance::ProvenanceInterner;
+    /// struct Provenance;
+    /// let mut interner = ProvenanceInterner::<Provenance, String>::new().unwrap();
+    ///
+    /// let sek = interner.intern_str("SEK");
+    /// assert_eq!(sek, interner.intern_str("SEK"));
+    /// assert_eq!(sek, interner.intern("SEK".to_string()));
+    /// ```
+    pub fn intern_str(&mut self, value: &str) -> Key<Provenance> {
+        match self.key_of(value) {
+            Some(key) => key,
+            None => self.insert(value.to_owned()),
+        }
+    }
+}
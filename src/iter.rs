@@ -0,0 +1,172 @@
+//! Iterators over the values of [ProvenanceMap](crate::ProvenanceMap) and
+//! [SeparateProvenanceMap](crate::SeparateProvenanceMap).
+
+use crate::{ProvenanceMap, SeparateProvenanceMap, Slot};
+
+/// An [iterator](Iterator) over immutable references to the values of a map,
+/// in the same order as [iter](SeparateProvenanceMap::iter).
+pub struct Iter<'a, Value> {
+    pub(crate) slots: std::slice::Iter<'a, Slot<Value>>,
+}
+
+impl<'a, Value> Iterator for Iter<'a, Value> {
+    type Item = &'a Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.by_ref().find_map(|slot| slot.value.as_ref())
+    }
+}
+
+/// An [iterator](Iterator) over mutable references to the values of a map,
+/// in the same order as [iter_mut](SeparateProvenanceMap::iter_mut).
+pub struct IterMut<'a, Value> {
+    pub(crate) slots: std::slice::IterMut<'a, Slot<Value>>,
+}
+
+impl<'a, Value> Iterator for IterMut<'a, Value> {
+    type Item = &'a mut Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.by_ref().find_map(|slot| slot.value.as_mut())
+    }
+}
+
+/// An [iterator](Iterator) moving the values out of a map, in the same order
+/// as [iter](SeparateProvenanceMap::iter).
+///
+/// The provenance of the map is released once the iterator is created.
+pub struct IntoIter<Value> {
+    pub(crate) slots: std::vec::IntoIter<Slot<Value>>,
+}
+
+impl<Value> Iterator for IntoIter<Value> {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.by_ref().find_map(|slot| slot.value)
+    }
+}
+
+impl<Provenance, Value> IntoIterator for SeparateProvenanceMap<Provenance, Value> {
+    type Item = Value;
+    type IntoIter = IntoIter<Value>;
+
+    /// Move the values out of the map.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, String>::new().unwrap();
+    ///
+    /// map.insert("Hello".to_string());
+    /// map.insert("world".to_string());
+    ///
+    /// let values: Vec<String> = map.into_iter().collect();
+    /// assert_eq!(vec!["Hello", "world"], values);
+    /// ```
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { slots: self.slots.into_iter() }
+    }
+}
+
+impl<'a, Provenance, Value> IntoIterator for &'a SeparateProvenanceMap<Provenance, Value> {
+    type Item = &'a Value;
+    type IntoIter = Iter<'a, Value>;
+
+    /// Iterate over immutable references to the values of the map.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    ///
+    /// let mut sum = 0;
+    /// for val in &map {
+    ///     sum += val;
+    /// }
+    /// assert_eq!(3, sum);
+    /// ```
+    fn into_iter(self) -> Self::IntoIter {
+        Iter { slots: self.slots.iter() }
+    }
+}
+
+impl<'a, Provenance, Value> IntoIterator for &'a mut SeparateProvenanceMap<Provenance, Value> {
+    type Item = &'a mut Value;
+    type IntoIter = IterMut<'a, Value>;
+
+    /// Iterate over mutable references to the values of the map.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// for val in &mut map {
+    ///     *val += 1;
+    /// }
+    /// assert_eq!(&2, map.get(one));
+    /// ```
+    fn into_iter(self) -> Self::IntoIter {
+        IterMut { slots: self.slots.iter_mut() }
+    }
+}
+
+impl<Value> IntoIterator for ProvenanceMap<Value> {
+    type Item = Value;
+    type IntoIter = IntoIter<Value>;
+
+    /// Move the values out of the map.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    ///
+    /// assert_eq!(vec![1, 2], map.into_iter().collect::<Vec<_>>());
+    /// ```
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter()
+    }
+}
+
+impl<'a, Value> IntoIterator for &'a ProvenanceMap<Value> {
+    type Item = &'a Value;
+    type IntoIter = Iter<'a, Value>;
+
+    /// Iterate over immutable references to the values of the map.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    ///
+    /// assert_eq!(3, (&map).into_iter().sum::<i32>());
+    /// ```
+    fn into_iter(self) -> Self::IntoIter {
+        (&self.map).into_iter()
+    }
+}
+
+impl<'a, Value> IntoIterator for &'a mut ProvenanceMap<Value> {
+    type Item = &'a mut Value;
+    type IntoIter = IterMut<'a, Value>;
+
+    /// Iterate over mutable references to the values of the map.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// for val in &mut map {
+    ///     *val += 1;
+    /// }
+    /// assert_eq!(&2, map.get(one));
+    /// ```
+    fn into_iter(self) -> Self::IntoIter {
+        (&mut self.map).into_iter()
+    }
+}
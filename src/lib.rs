@@ -45,6 +45,98 @@
 //! assert!(map.is_none()); // Creation failed and `None` were returned
 //! ```
 //!
+//! Where the reason of a failure matters, [try_new](ProvenanceMap::try_new) returns
+//! a [ProvenanceError](ProvenanceError) instead.
+//! ```
+//! use provenance::ProvenanceMap;
+//!
+//! let map = ProvenanceMap::<i32>::try_new().unwrap();
+//!
+//! let error = ProvenanceMap::<i32>::try_new().err().unwrap();
+//! assert_eq!("provenance `i32` is already used by another map", error.to_string());
+//! ```
+//!
+//! The uniqueness only concerns maps that exist at the same time. Dropping a map
+//! releases its provenance, after which a new map with that provenance may be created.
+//! ```
+//! use provenance::ProvenanceMap;
+//!
+//! let map = ProvenanceMap::<i32>::new();
+//! assert!(map.is_some());
+//! drop(map);
+//!
+//! // The provenance were released when the first map were dropped
+//! let map = ProvenanceMap::<i32>::new();
+//! assert!(map.is_some());
+//! ```
+//!
+//! # Removal
+//! Values may be removed from a map. The key of a removed value is said to be
+//! _stale_. Retrieving a value with a stale key panics, but the key is never
+//! mistaken for the key of another value. [try_get](SeparateProvenanceMap::try_get)
+//! may be used where a key might be stale.
+//! ```
+//! use provenance::ProvenanceMap;
+//!
+//! let mut map = ProvenanceMap::<i32>::new().unwrap();
+//! let key = map.insert(5);
+//!
+//! assert_eq!(5, map.remove(key));
+//! assert_eq!(None, map.try_get(key));
+//! ```
+//!
+//! # Branded maps
+//! Maps that only are needed for a limited part of a program may instead use a
+//! unique lifetime as provenance. Such maps are created with [scope](scope), and
+//! do not need the registry of used provenance. Hence, their creation can not fail.
+//! ```
+//! let name = provenance::scope(|mut names| {
+//!     let key = names.insert(String::from("Jesper"));
+//!     names.get(key).clone()
+//! });
+//!
+//! assert_eq!("Jesper", name);
+//! ```
+//!
+//! # Declaring maps
+//! The [provenance!](provenance!) macro declares a map type that is its own
+//! provenance, along with an alias for its keys and, if asked to, a singleton.
+//! ```
+//! provenance::provenance! {
+//!     struct Names(Map<String>);
+//!     type NameKey;
+//! }
+//!
+//! let mut names = Names::new().unwrap();
+//! let key: NameKey = names.insert(String::from("Jesper"));
+//! assert_eq!("Jesper", names.get(key));
+//! ```
+//!
+//! # Concurrent maps
+//! A [ConcurrentProvenanceMap](ConcurrentProvenanceMap) may be inserted into through
+//! a shared reference, and thus be shared between threads without a lock.
+//! ```
+//! use provenance::ConcurrentProvenanceMap;
+//!
+//! struct Names;
+//! let names = ConcurrentProvenanceMap::<Names, &str>::new().unwrap();
+//!
+//! let key = std::thread::scope(|s| s.spawn(|| names.insert("Jesper")).join().unwrap());
+//! assert_eq!(&"Jesper", names.get(key));
+//! ```
+//!
+//! A [GlobalProvenanceMap](GlobalProvenanceMap) is a concurrent map that may be
+//! declared in a `static`, and claims its provenance when first used.
+//! ```
+//! use provenance::GlobalProvenanceMap;
+//!
+//! struct Names;
+//! static NAMES: GlobalProvenanceMap<Names, &str> = GlobalProvenanceMap::new();
+//!
+//! let key = NAMES.insert("Jesper");
+//! assert_eq!(&"Jesper", NAMES.get(key));
+//! ```
+//!
 //! # Lightweight keys
 //! The keys generated by this library's maps are ligthweight in the sense
 //! that they are copiable. This means that other copiable values can link
@@ -78,17 +170,59 @@
 //! assert_eq!(sek, sum.currency);
 //! assert_eq!("Swedish Krona".to_string(), currencies.get(sum.currency).name);
 //! ```
+//!
+//! Values linking to other maps this way may declare so by implementing [Relation],
+//! such that their maps can be [joined](ProvenanceMap::join) with the maps linked to.
+
+mod append_only;
+mod branded;
+mod buckets;
+mod checkpoint;
+mod concurrent;
+mod error;
+mod global;
+mod indexed;
+mod interner;
+pub mod iter;
+mod macros;
+mod observable;
+mod persistent;
+mod registry;
+mod relation;
+pub mod secondary;
+mod snapshot;
+mod tracked;
+mod transaction;
+#[cfg(feature = "serde")]
+mod serialization;
 
 use std::{
-    collections::HashSet,
+    cmp::Ordering,
+    collections::BTreeMap,
     marker::PhantomData,
-    any::{TypeId},
     fmt::{Debug, Formatter},
-    sync::Mutex,
-    ops::DerefMut,
-    hash::{Hash, Hasher}
+    hash::{Hash, Hasher},
+    ops::{Bound, Deref, Index, IndexMut, RangeBounds},
 };
-use lazy_static::lazy_static;
+use registry::Claim;
+
+pub use append_only::AppendOnlyProvenanceMap;
+pub use branded::{scope, Brand, BrandedKey, BrandedMap};
+pub use checkpoint::{Checkpoint, Committed, Speculative, SpeculativeKey};
+pub use concurrent::ConcurrentProvenanceMap;
+pub use error::ProvenanceError;
+pub use global::GlobalProvenanceMap;
+pub use indexed::{DuplicateIndexKey, IndexConflict, IndexedMut, IndexedProvenanceMap, MultiIndex, UniqueIndex};
+pub use interner::ProvenanceInterner;
+pub use observable::{ObservableProvenanceMap, ObservedMut, Subscription};
+pub use persistent::PersistentProvenanceMap;
+pub use relation::Relation;
+pub use secondary::{CompleteSecondaryMap, SecondaryMap};
+pub use snapshot::Snapshot;
+pub use tracked::{ChangeEvent, TrackedProvenanceMap};
+pub use transaction::Transaction;
+#[cfg(feature = "serde")]
+pub use serialization::Remap;
 
 /// A provenance map is a map-like data structure that know which keys belong
 /// to which map.
@@ -116,29 +250,35 @@ use lazy_static::lazy_static;
 ///
 /// However, if it were possible to create multiple maps with the same type, e.g.
 /// `ProvenanceMap<String>` the type of the key wouldn't be enough to track what map
-/// a key came from. Therefore, it is only possible to create a single map per
-/// concrete type given for the `Value` type parameter.
+/// a key came from. Therefore, only a single map per concrete type given for the
+/// `Value` type parameter may exist at a time.
 /// ```
 /// use provenance::ProvenanceMap;
 ///
 /// // Creating a map once is OK
-/// let mut map = ProvenanceMap::<String>::new();
+/// let map = ProvenanceMap::<String>::new();
 /// assert!(map.is_some());
 ///
 /// // Creating another map with the same signature is not OK
-/// let mut map = ProvenanceMap::<String>::new();
-/// assert!(map.is_none());
+/// let other = ProvenanceMap::<String>::new();
+/// assert!(other.is_none());
+///
+/// // Once the first map is dropped, the map may be created again
+/// drop(map);
+/// let map = ProvenanceMap::<String>::new();
+/// assert!(map.is_some());
 /// ```
 ///
-
+/// The keys of a dropped map are not accepted by a map created after it, see
+/// [SeparateProvenanceMap::get](SeparateProvenanceMap::get).
 pub struct ProvenanceMap<Value> {
     map: SeparateProvenanceMap<Value, Value>
 }
 
 impl<Value: 'static> ProvenanceMap<Value> {
 
-    /// Create a new map if one with the given signature have not already been created.
-    /// If one has, [`None`](std::option::Option::None) is returned.
+    /// Create a new map, unless a map with the given signature already exists.
+    /// If one does, [`None`](std::option::Option::None) is returned.
     /// ```
     /// use provenance::ProvenanceMap;
     ///
@@ -147,13 +287,38 @@ impl<Value: 'static> ProvenanceMap<Value> {
     /// assert!(map.is_some());
     ///
     /// // Creating another map with the same signature is not OK
-    /// let map = ProvenanceMap::<String>::new();
-    /// assert!(map.is_none());
+    /// let other = ProvenanceMap::<String>::new();
+    /// assert!(other.is_none());
+    ///
+    /// // Unless the first map has been dropped
+    /// drop(map);
+    /// assert!(ProvenanceMap::<String>::new().is_some());
     /// ```
     pub fn new() -> Option<ProvenanceMap<Value>> {
-        let map = SeparateProvenanceMap::new()?;
+        ProvenanceMap::try_new().ok()
+    }
+
+    /// Create a new map, unless a map with the given signature already exists.
+    /// If one does, or the map could not be created for another reason, an
+    /// [error](ProvenanceError) describing why is returned.
+    /// ```
+    /// use provenance::{ProvenanceError, ProvenanceMap};
+    ///
+    /// fn start() -> Result<(), ProvenanceError> {
+    ///     let map = ProvenanceMap::<String>::try_new()?;
+    ///     let map = ProvenanceMap::<String>::try_new()?;
+    ///     Ok(())
+    /// }
+    ///
+    /// assert_eq!(
+    ///     "provenance `alloc::string::String` is already used by another map",
+    ///     start().unwrap_err().to_string(),
+    /// );
+    /// ```
+    pub fn try_new() -> Result<ProvenanceMap<Value>, ProvenanceError> {
+        let map = SeparateProvenanceMap::try_new()?;
 
-        Some(ProvenanceMap {
+        Ok(ProvenanceMap {
             map
         })
     }
@@ -179,10 +344,83 @@ impl<Value: 'static> ProvenanceMap<Value> {
     /// let key2 = map.insert(5);
     /// assert_ne!(key1, key2);
     /// ```
+    ///
+    /// # Panics
+    /// Panics if the map has no room for another value, see [try_insert](ProvenanceMap::try_insert).
     pub fn insert(&mut self, value: Value) -> Key<Value> {
         self.map.insert(value)
     }
 
+    /// Insert a value into the map, unless the map has no room for another value.
+    /// See [SeparateProvenanceMap::try_insert](SeparateProvenanceMap::try_insert).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let key = map.try_insert(5).unwrap();
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    pub fn try_insert(&mut self, value: Value) -> Result<Key<Value>, ProvenanceError> {
+        self.map.try_insert(value)
+    }
+
+    /// Insert a value into the map, and get an [owned key](OwnedKey) to it.
+    /// The value may only be removed by giving up the owned key.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// assert_eq!(&5, map.get_owned(&owned));
+    /// ```
+    pub fn insert_owned(&mut self, value: Value) -> OwnedKey<Value> {
+        self.map.insert_owned(value)
+    }
+
+    /// Insert each value of an [iterator](IntoIterator) into the map, returning
+    /// their keys in the same order.
+    ///
+    /// Unlike [extend](Extend::extend), which discards the keys.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let keys = map.extend_keys(vec![1, 2, 3]);
+    /// assert_eq!(vec![&1, &2, &3], keys.iter().map(|&key| map.get(key)).collect::<Vec<_>>());
+    /// ```
+    pub fn extend_keys<I: IntoIterator<Item = Value>>(&mut self, values: I) -> Vec<Key<Value>> {
+        self.map.extend_keys(values)
+    }
+
+    /// Get the number of values in the map.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let key = map.insert(1);
+    /// map.insert(2);
+    /// assert_eq!(2, map.len());
+    ///
+    /// map.remove(key);
+    /// assert_eq!(1, map.len());
+    /// ```
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Check whether the map contains no values.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// assert!(map.is_empty());
+    ///
+    /// map.insert(1);
+    /// assert!(!map.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Use a [key](Key) to retrieve an immutable reference to
     /// a stored value.
     /// ```
@@ -209,7 +447,117 @@ impl<Value: 'static> ProvenanceMap<Value> {
         self.map.get_mut(key)
     }
 
-    /// Get an [iterator](Iterator) over all keys in the map.
+    /// Use a [key](Key) to retrieve an immutable reference to
+    /// a stored value, if the value still is in the map.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let key = map.insert(15);
+    /// assert_eq!(Some(&15), map.try_get(key));
+    ///
+    /// map.remove(key);
+    /// assert_eq!(None, map.try_get(key));
+    /// ```
+    pub fn try_get(&self, key: Key<Value>) -> Option<&Value> {
+        self.map.try_get(key)
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to
+    /// a stored value, if the value still is in the map.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let key = map.insert(15);
+    /// assert_eq!(Some(&mut 15), map.try_get_mut(key));
+    ///
+    /// map.remove(key);
+    /// assert_eq!(None, map.try_get_mut(key));
+    /// ```
+    pub fn try_get_mut(&mut self, key: Key<Value>) -> Option<&mut Value> {
+        self.map.try_get_mut(key)
+    }
+
+    /// Use an [owned key](OwnedKey) to retrieve an immutable reference to its value.
+    /// See [SeparateProvenanceMap::get_owned](SeparateProvenanceMap::get_owned).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(15);
+    /// assert_eq!(&15, map.get_owned(&owned));
+    /// ```
+    pub fn get_owned(&self, key: &OwnedKey<Value>) -> &Value {
+        self.map.get_owned(key)
+    }
+
+    /// Use an [owned key](OwnedKey) to retrieve a mutable reference to its value.
+    /// See [SeparateProvenanceMap::get_owned_mut](SeparateProvenanceMap::get_owned_mut).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(15);
+    /// *map.get_owned_mut(&owned) += 1;
+    /// assert_eq!(&16, map.get_owned(&owned));
+    /// ```
+    pub fn get_owned_mut(&mut self, key: &OwnedKey<Value>) -> &mut Value {
+        self.map.get_owned_mut(key)
+    }
+
+    /// Remove a value from the map, returning it.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let key = map.insert(15);
+    /// assert_eq!(15, map.remove(key));
+    /// assert_eq!(None, map.try_get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value already has been removed, or if it were inserted with
+    /// [insert_owned](ProvenanceMap::insert_owned).
+    pub fn remove(&mut self, key: Key<Value>) -> Value {
+        self.map.remove(key)
+    }
+
+    /// Remove a value inserted with [insert_owned](ProvenanceMap::insert_owned),
+    /// returning it. See [SeparateProvenanceMap::remove_owned](SeparateProvenanceMap::remove_owned).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// assert_eq!(5, map.remove_owned(owned));
+    /// ```
+    pub fn remove_owned(&mut self, key: OwnedKey<Value>) -> Value {
+        self.map.remove_owned(key)
+    }
+
+    /// Only keep the values for which the predicate returns `true`.
+    ///
+    /// Values inserted with [insert_owned](ProvenanceMap::insert_owned) are always
+    /// kept, and are not given to the predicate.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.insert(3);
+    ///
+    /// map.retain(|_, val| *val % 2 == 1);
+    /// assert_eq!(4, map.iter().sum());
+    /// ```
+    pub fn retain<P: FnMut(Key<Value>, &mut Value) -> bool>(&mut self, predicate: P) {
+        self.map.retain(predicate)
+    }
+
+    /// Get an [iterator](Iterator) over all keys in the map, except those of values
+    /// inserted with [insert_owned](ProvenanceMap::insert_owned).
+    /// See [SeparateProvenanceMap::keys](SeparateProvenanceMap::keys).
     /// ```
     /// use provenance::ProvenanceMap;
     /// let mut map = ProvenanceMap::<i32>::new().unwrap();
@@ -220,7 +568,7 @@ impl<Value: 'static> ProvenanceMap<Value> {
     ///
     /// assert_eq!(3, map.keys().count());
     /// ```
-    pub fn keys(&self) -> impl Iterator<Item = Key<Value>> {
+    pub fn keys(&self) -> impl Iterator<Item = Key<Value>> + '_ {
         self.map.keys()
     }
 
@@ -257,7 +605,7 @@ impl<Value: 'static> ProvenanceMap<Value> {
         self.map.iter_mut()
     }
 
-    /// Search the map in insertion order for the first value that satisfy the given predicate.
+    /// Search the map in [slot order](SeparateProvenanceMap::iter) for the first value that satisfy the given predicate.
     /// If such value is found, an immutable reference to it is returned,
     /// ```
     /// use provenance::ProvenanceMap;
@@ -284,7 +632,7 @@ impl<Value: 'static> ProvenanceMap<Value> {
         self.map.find(predicate)
     }
 
-    /// Search the map in insertion order for the first value that satisfy the given predicate.
+    /// Search the map in [slot order](SeparateProvenanceMap::iter) for the first value that satisfy the given predicate.
     /// If such value is found, a mutable reference to it is returned,
     /// ```
     /// use provenance::ProvenanceMap;
@@ -310,6 +658,162 @@ impl<Value: 'static> ProvenanceMap<Value> {
     pub fn find_mut<P: Fn(&Value) -> bool>(&mut self, predicate: P) -> Option<&mut Value> {
         self.map.find_mut(predicate)
     }
+
+    /// Get an [iterator](Iterator) over the keys of the map together with immutable
+    /// references to their values.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// let two = map.insert(2);
+    ///
+    /// let pairs: Vec<_> = map.iter_with_keys().collect();
+    /// assert_eq!(vec![(one, &1), (two, &2)], pairs);
+    /// ```
+    pub fn iter_with_keys(&self) -> impl Iterator<Item = (Key<Value>, &Value)> {
+        self.map.iter_with_keys()
+    }
+
+    /// Get an [iterator](Iterator) over the keys of the map together with mutable
+    /// references to their values.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// let two = map.insert(2);
+    ///
+    /// // Add ten to the value of `one` only
+    /// for (key, val) in map.iter_mut_with_keys() {
+    ///     if key == one {
+    ///         *val += 10;
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(&11, map.get(one));
+    /// assert_eq!(&2, map.get(two));
+    /// ```
+    pub fn iter_mut_with_keys(&mut self) -> impl Iterator<Item = (Key<Value>, &mut Value)> {
+        self.map.iter_mut_with_keys()
+    }
+
+    /// Search the map in [slot order](SeparateProvenanceMap::iter) for the first value that satisfy the given predicate.
+    /// If such value is found, its key is returned,
+    /// otherwise [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// let two = map.insert(2);
+    ///
+    /// assert_eq!(Some(two), map.find_key(|&val| val == 2));
+    /// assert_eq!(None, map.find_key(|&val| val == 53));
+    /// ```
+    pub fn find_key<P: Fn(&Value) -> bool>(&self, predicate: P) -> Option<Key<Value>> {
+        self.map.find_key(predicate)
+    }
+
+    /// Search the map in [slot order](SeparateProvenanceMap::iter) for the first value that satisfy the given predicate.
+    /// If such value is found, its position among the values [iterated](Self::iter) over is returned,
+    /// otherwise [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.insert(3);
+    ///
+    /// assert_eq!(Some(1), map.position(|&val| val == 2));
+    /// assert_eq!(None, map.position(|&val| val == 53));
+    /// ```
+    pub fn position<P: Fn(&Value) -> bool>(&self, predicate: P) -> Option<usize> {
+        self.map.position(predicate)
+    }
+
+    /// Get an [iterator](Iterator) over the keys of every value that satisfy the
+    /// given predicate, in [slot order](SeparateProvenanceMap::iter).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// map.insert(2);
+    /// let three = map.insert(3);
+    ///
+    /// let odd: Vec<_> = map.filter_keys(|&val| val % 2 == 1).collect();
+    /// assert_eq!(vec![one, three], odd);
+    /// ```
+    pub fn filter_keys<'a, P: Fn(&Value) -> bool + 'a>(&'a self, predicate: P) -> impl Iterator<Item = Key<Value>> + 'a {
+        self.map.filter_keys(predicate)
+    }
+
+    /// Get an [iterator](Iterator) over the keys within a range, together with immutable
+    /// references to their values. The keys are visited in [order](Key#ordering), i.e.
+    /// in the order their values were inserted.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// let two = map.insert(2);
+    /// let three = map.insert(3);
+    ///
+    /// let values: Vec<_> = map.range(one..three).map(|(_, val)| *val).collect();
+    /// assert_eq!(vec![1, 2], values);
+    /// ```
+    pub fn range<R: RangeBounds<Key<Value>>>(&self, range: R) -> impl Iterator<Item = (Key<Value>, &Value)> {
+        self.map.range(range)
+    }
+
+    /// Get an [iterator](Iterator) over the keys of the values inserted after the value
+    /// of a key, in the order they were inserted.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// let checkpoint = map.insert(2);
+    /// let three = map.insert(3);
+    /// let four = map.insert(4);
+    ///
+    /// assert_eq!(vec![three, four], map.keys_after(checkpoint).collect::<Vec<_>>());
+    /// ```
+    pub fn keys_after(&self, key: Key<Value>) -> impl Iterator<Item = Key<Value>> + '_ {
+        self.map.keys_after(key)
+    }
+
+    /// Get the key of the value that were inserted first among the values in the map.
+    /// If the map is empty, [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// assert_eq!(None, map.first_key());
+    ///
+    /// let one = map.insert(1);
+    /// map.insert(2);
+    /// assert_eq!(Some(one), map.first_key());
+    /// ```
+    pub fn first_key(&self) -> Option<Key<Value>> {
+        self.map.first_key()
+    }
+
+    /// Get the key of the value that were inserted last among the values in the map.
+    /// If the map is empty, [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// assert_eq!(None, map.last_key());
+    ///
+    /// map.insert(1);
+    /// let two = map.insert(2);
+    /// assert_eq!(Some(two), map.last_key());
+    /// ```
+    pub fn last_key(&self) -> Option<Key<Value>> {
+        self.map.last_key()
+    }
 }
 
 /// A [ProvenanceMap](ProvenanceMap) where the a type separate from the type of the stored
@@ -352,23 +856,103 @@ impl<Value: 'static> ProvenanceMap<Value> {
 /// let mut map = SeparateProvenanceMap::<i32, bool>::new();
 /// assert!(map.is_none());
 /// ```
-pub struct SeparateProvenanceMap<Provenance, Value> {
-    elements: Vec<Value>,
-    _pd: PhantomData<Provenance>,
-}
-
-impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Value> {
-
-    /// Creates a new empty map with some type as provenance.
-    ///
-    /// If a map with such provenance already has been created, [`None`](std::option::Option::None) will be returned.
-    /// Thus be careful to not drop maps unintentionally.
-    /// ```
-    /// use provenance::SeparateProvenanceMap;
-    ///
-    /// struct Provenance;
-    ///
-    /// // Creating a map with some type as provenance is ok
+///
+/// # Cloning
+/// Maps do not implement [Clone](Clone), since a clone would be a second map with the
+/// same provenance, and keys of one would be accepted by the other. A read-only copy
+/// that shares the provenance may instead be taken with [snapshot](SeparateProvenanceMap::snapshot).
+/// To copy the values into a map with another provenance, [extend](Extend::extend)
+/// that map with them.
+/// ```compile_fail
+/// use provenance::SeparateProvenanceMap;
+/// struct Provenance;
+/// let map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+///
+/// let copy = map.clone();
+/// ```
+/// ```
+/// use provenance::SeparateProvenanceMap;
+/// struct Original;
+/// struct Copy;
+///
+/// let mut original = SeparateProvenanceMap::<Original, i32>::new().unwrap();
+/// original.extend(vec![1, 2, 3]);
+///
+/// let mut copy = SeparateProvenanceMap::<Copy, i32>::new().unwrap();
+/// copy.extend(original.iter().cloned());
+/// assert_eq!(6, copy.iter().sum());
+/// ```
+///
+/// # Default
+/// Maps do not implement [Default](Default) either, since creating a map fails while
+/// another map has its provenance, and a default map could only panic then. Maps
+/// are created with [new](SeparateProvenanceMap::new) or [try_new](SeparateProvenanceMap::try_new),
+/// which report the failure instead.
+/// ```compile_fail
+/// use provenance::SeparateProvenanceMap;
+/// struct Provenance;
+///
+/// let map = SeparateProvenanceMap::<Provenance, i32>::default();
+/// ```
+///
+/// # Thread safety
+/// The provenance never is stored in the map, so whether the map is [Send](Send)
+/// and [Sync](Sync) only depends on the type of the stored values.
+/// ```
+/// use provenance::SeparateProvenanceMap;
+/// use std::rc::Rc;
+///
+/// fn assert_send_sync<T: Send + Sync>() {}
+///
+/// assert_send_sync::<SeparateProvenanceMap<Rc<()>, i32>>();
+/// ```
+/// ```compile_fail
+/// use provenance::SeparateProvenanceMap;
+/// use std::rc::Rc;
+///
+/// fn assert_send<T: Send>() {}
+///
+/// assert_send::<SeparateProvenanceMap<i32, Rc<()>>>();
+/// ```
+pub struct SeparateProvenanceMap<Provenance, Value> {
+    slots: Vec<Slot<Value>>,
+    vacant: Vec<usize>,
+    /// The slot of each value by its generation, i.e. the values in insertion order,
+    /// for visiting them in the [order](Key#ordering) of their keys.
+    order: BTreeMap<u64, usize>,
+    /// Identity of this map, telling it apart from earlier and later maps with the
+    /// same provenance.
+    instance: u64,
+    // Only held to release the provenance when the map is dropped.
+    _claim: Claim,
+    _pd: PhantomData<fn() -> Provenance>,
+}
+
+/// Storage for one value of a [SeparateProvenanceMap](SeparateProvenanceMap).
+///
+/// The generation is that of the value that most recently occupied the slot.
+/// Owned values may only be removed through their [OwnedKey](OwnedKey).
+struct Slot<Value> {
+    generation: u64,
+    owned: bool,
+    value: Option<Value>,
+}
+
+pub(crate) const REMOVED_VALUE: &str = "key refers to a value that is no longer in the map";
+const OWNED_VALUE: &str = "key refers to a value that only may be removed through its owned key";
+const FOREIGN_OWNED_KEY: &str = "owned key were created by another map with the same provenance";
+
+impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Value> {
+
+    /// Creates a new empty map with some type as provenance.
+    ///
+    /// If a map with such provenance already exists, [`None`](std::option::Option::None) will be returned.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    ///
+    /// struct Provenance;
+    ///
+    /// // Creating a map with some type as provenance is ok
     /// let map = SeparateProvenanceMap::<Provenance, bool>::new();
     /// assert!(map.is_some());
     ///
@@ -376,27 +960,51 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
     /// let map = SeparateProvenanceMap::<Provenance, i32>::new();
     /// assert!(map.is_none());
     /// ```
+    ///
+    /// When a map is dropped its provenance is released, and a new map may be
+    /// created with it.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    ///
+    /// struct Provenance;
+    ///
+    /// let map = SeparateProvenanceMap::<Provenance, bool>::new();
+    /// assert!(map.is_some());
+    /// drop(map);
+    ///
+    /// let map = SeparateProvenanceMap::<Provenance, bool>::new();
+    /// assert!(map.is_some());
+    /// ```
     pub fn new() -> Option<SeparateProvenanceMap<Provenance, Value>> {
-        lazy_static! {
-            static ref USED_PROVENANCE: Mutex<HashSet<TypeId>> = Mutex::new(Default::default());
-        }
-
-        let used_maps: &Mutex<HashSet<TypeId>> = &*USED_PROVENANCE;
-        let mut lock = used_maps.lock().unwrap();
-        let used_maps = lock.deref_mut();
-
+        SeparateProvenanceMap::try_new().ok()
+    }
 
-        let type_id = TypeId::of::<Provenance>();
+    /// Creates a new empty map with some type as provenance.
+    ///
+    /// If a map with such provenance already exists, or the map could not be created
+    /// for another reason, an [error](ProvenanceError) describing why is returned.
+    /// ```
+    /// use provenance::{ProvenanceError, SeparateProvenanceMap};
+    ///
+    /// struct Provenance;
+    ///
+    /// let map = SeparateProvenanceMap::<Provenance, bool>::try_new();
+    /// assert!(map.is_ok());
+    ///
+    /// let map = SeparateProvenanceMap::<Provenance, bool>::try_new();
+    /// assert!(matches!(map, Err(ProvenanceError::InUse { .. })));
+    /// ```
+    pub fn try_new() -> Result<SeparateProvenanceMap<Provenance, Value>, ProvenanceError> {
+        let claim = Claim::acquire::<Provenance>()?;
 
-        if used_maps.contains(&type_id) {
-            None
-        } else {
-            used_maps.insert(type_id);
-            Some(SeparateProvenanceMap {
-                elements: vec![],
-                _pd: Default::default()
-            })
-        }
+        Ok(SeparateProvenanceMap {
+            slots: vec![],
+            vacant: vec![],
+            order: BTreeMap::new(),
+            instance: registry::fresh_generation(),
+            _claim: claim,
+            _pd: Default::default()
+        })
     }
 
     /// Insert a value into this map.
@@ -420,10 +1028,126 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
     /// let key2 = map.insert(5);
     /// assert_ne!(key1, key2);
     /// ```
+    ///
+    /// # Panics
+    /// Panics if the map has no room for another value, see
+    /// [try_insert](SeparateProvenanceMap::try_insert).
     pub fn insert(&mut self, value: Value) -> Key<Provenance> {
-        let index = self.elements.len();
-        self.elements.insert(index, value);
-        Key::new(index)
+        self.try_insert(value).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Insert a value into this map, unless the map has no room for another value,
+    /// e.g. as memory for it could not be allocated.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.try_insert(5).unwrap();
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    pub fn try_insert(&mut self, value: Value) -> Result<Key<Provenance>, ProvenanceError> {
+        self.insert_slot(value, false)
+    }
+
+    /// Insert a value into this map, and get an [owned key](OwnedKey) to it.
+    ///
+    /// The value may only be removed by giving up the owned key, which guarantees
+    /// that the value remains in the map for as long as the owned key exists.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// assert_eq!(&5, map.get_owned(&owned));
+    ///
+    /// assert_eq!(5, map.remove_owned(owned));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the map has no room for another value, like [insert](SeparateProvenanceMap::insert).
+    pub fn insert_owned(&mut self, value: Value) -> OwnedKey<Provenance> {
+        let key = self.insert_slot(value, true).unwrap_or_else(|error| panic!("{}", error));
+
+        OwnedKey {
+            key,
+            instance: self.instance,
+        }
+    }
+
+    /// Insert each value of an [iterator](IntoIterator) into the map, returning
+    /// their keys in the same order.
+    ///
+    /// Unlike [extend](Extend::extend), which discards the keys.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let keys = map.extend_keys(vec![1, 2, 3]);
+    /// assert_eq!(vec![&1, &2, &3], keys.iter().map(|&key| map.get(key)).collect::<Vec<_>>());
+    /// ```
+    pub fn extend_keys<I: IntoIterator<Item = Value>>(&mut self, values: I) -> Vec<Key<Provenance>> {
+        values.into_iter()
+            .map(|value| self.insert(value))
+            .collect()
+    }
+
+    /// Get the number of values in the map.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(1);
+    /// map.insert(2);
+    /// assert_eq!(2, map.len());
+    ///
+    /// map.remove(key);
+    /// assert_eq!(1, map.len());
+    /// ```
+    pub fn len(&self) -> usize {
+        // Every slot without a value is vacant.
+        self.slots.len() - self.vacant.len()
+    }
+
+    /// Check whether the map contains no values.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// assert!(map.is_empty());
+    ///
+    /// map.insert(1);
+    /// assert!(!map.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn insert_slot(&mut self, value: Value, owned: bool) -> Result<Key<Provenance>, ProvenanceError> {
+        let slot = Slot {
+            generation: registry::fresh_generation(),
+            owned,
+            value: Some(value),
+        };
+        let generation = slot.generation;
+
+        let index = match self.vacant.pop() {
+            Some(index) => {
+                self.slots[index] = slot;
+                index
+            }
+            None => {
+                self.slots.try_reserve(1).map_err(|_| error::capacity_exceeded::<Provenance>())?;
+                self.slots.push(slot);
+                self.slots.len() - 1
+            }
+        };
+        self.order.insert(generation, index);
+
+        Ok(Key::new(index, generation))
     }
 
     /// Use a [key](Key) to retrieve an immutable reference to a stored value.
@@ -435,11 +1159,27 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
     /// let key = map.insert(5);
     /// assert_eq!(&5, map.get(key));
     /// ```
+    ///
+    /// # Panics
+    /// Panics if the value has been [removed](SeparateProvenanceMap::remove), or if
+    /// the key were created by an earlier, since dropped, map with the same provenance.
+    /// ```should_panic
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    ///
+    /// let mut old = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// let key = old.insert(5);
+    /// drop(old);
+    ///
+    /// let mut new = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// new.insert(7);
+    /// new.get(key); // The key belongs to the dropped map
+    /// ```
     pub fn get(&self, key: Key<Provenance>) -> &Value {
         // The key has the correct provenance,
         // thus we know that we created it in `insert`,
-        // thus it is safe to use.
-        &self.elements[key.index]
+        // thus it is safe to use as long as its value remains.
+        self.try_get(key).expect(REMOVED_VALUE)
     }
 
     /// Use a [key](Key) to retrieve a mutable reference to a stored value.
@@ -451,14 +1191,247 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
     /// let key = map.insert(5);
     /// assert_eq!(&mut 5, map.get_mut(key));
     /// ```
+    ///
+    /// # Panics
+    /// Panics if the value has been [removed](SeparateProvenanceMap::remove), or if
+    /// the key were created by an earlier, since dropped, map with the same provenance.
     pub fn get_mut(&mut self, key: Key<Provenance>) -> &mut Value {
         // The key has the correct provenance,
         // thus we know that we created it in `insert`,
-        // thus it is safe to use.
-        &mut self.elements[key.index]
+        // thus it is safe to use as long as its value remains.
+        self.try_get_mut(key).expect(REMOVED_VALUE)
+    }
+
+    /// Use a [key](Key) to retrieve an immutable reference to a stored value,
+    /// if the value still is in the map.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// assert_eq!(Some(&5), map.try_get(key));
+    ///
+    /// map.remove(key);
+    /// assert_eq!(None, map.try_get(key));
+    /// ```
+    pub fn try_get(&self, key: Key<Provenance>) -> Option<&Value> {
+        self.slots.get(key.index)
+            .filter(|slot| slot.generation == key.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to a stored value,
+    /// if the value still is in the map.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// assert_eq!(Some(&mut 5), map.try_get_mut(key));
+    ///
+    /// map.remove(key);
+    /// assert_eq!(None, map.try_get_mut(key));
+    /// ```
+    pub fn try_get_mut(&mut self, key: Key<Provenance>) -> Option<&mut Value> {
+        self.slots.get_mut(key.index)
+            .filter(|slot| slot.generation == key.generation)
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    /// Use an [owned key](OwnedKey) to retrieve an immutable reference to its value.
+    ///
+    /// Unlike a plain key, the owned key guarantees that its value is in the map.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// assert_eq!(&5, map.get_owned(&owned));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the owned key were created by another map with the same provenance,
+    /// see [remove_owned](SeparateProvenanceMap::remove_owned).
+    pub fn get_owned(&self, key: &OwnedKey<Provenance>) -> &Value {
+        assert_eq!(self.instance, key.instance, "{}", FOREIGN_OWNED_KEY);
+        self.get(key.key)
+    }
+
+    /// Use an [owned key](OwnedKey) to retrieve a mutable reference to its value.
+    ///
+    /// Unlike a plain key, the owned key guarantees that its value is in the map.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// *map.get_owned_mut(&owned) += 1;
+    /// assert_eq!(&6, map.get_owned(&owned));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the owned key were created by another map with the same provenance,
+    /// see [remove_owned](SeparateProvenanceMap::remove_owned).
+    pub fn get_owned_mut(&mut self, key: &OwnedKey<Provenance>) -> &mut Value {
+        assert_eq!(self.instance, key.instance, "{}", FOREIGN_OWNED_KEY);
+        self.get_mut(key.key)
+    }
+
+    /// Remove a value from the map, returning it.
+    ///
+    /// The key of the value, and every copy of it, will from then on not be
+    /// accepted by [get](SeparateProvenanceMap::get), even if the space of the
+    /// removed value is reused by a later insertion.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let old = map.insert(5);
+    /// assert_eq!(5, map.remove(old));
+    ///
+    /// let new = map.insert(7);
+    /// assert_ne!(old, new);
+    /// assert_eq!(None, map.try_get(old));
+    /// assert_eq!(&7, map.get(new));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value already has been removed.
+    /// ```should_panic
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// map.remove(key);
+    /// map.remove(key); // The value is already removed
+    /// ```
+    ///
+    /// Also panics if the value were inserted with [insert_owned](SeparateProvenanceMap::insert_owned),
+    /// as such values only may be removed with [remove_owned](SeparateProvenanceMap::remove_owned).
+    /// ```should_panic
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// let key = *owned.borrow();
+    /// map.remove(key); // The value is owned by `owned`
+    /// ```
+    pub fn remove(&mut self, key: Key<Provenance>) -> Value {
+        let owned = matches!(
+            self.slots.get(key.index),
+            Some(slot) if slot.generation == key.generation && slot.owned
+        );
+        assert!(!owned, "{}", OWNED_VALUE);
+
+        self.take(key)
+    }
+
+    /// Remove a value inserted with [insert_owned](SeparateProvenanceMap::insert_owned),
+    /// returning it.
+    ///
+    /// The owned key is consumed, and no one else may remove its value, so the value
+    /// always is in the map.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// assert_eq!(5, map.remove_owned(owned));
+    /// assert!(map.is_empty());
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the owned key were created by another map with the same provenance,
+    /// e.g. an earlier map that since has been dropped.
+    /// ```should_panic
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    ///
+    /// let mut old = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// let owned = old.insert_owned(5);
+    /// drop(old);
+    ///
+    /// let mut new = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// new.remove_owned(owned); // The owned key belongs to the dropped map
+    /// ```
+    pub fn remove_owned(&mut self, key: OwnedKey<Provenance>) -> Value {
+        assert_eq!(self.instance, key.instance, "{}", FOREIGN_OWNED_KEY);
+        self.take(key.key)
+    }
+
+    /// Take the value referenced by a key out of its slot, without regard to
+    /// whether it is owned.
+    fn take(&mut self, key: Key<Provenance>) -> Value {
+        let value = self.slots.get_mut(key.index)
+            .filter(|slot| slot.generation == key.generation)
+            .and_then(|slot| slot.value.take())
+            .expect(REMOVED_VALUE);
+
+        self.vacant.push(key.index);
+        self.order.remove(&key.generation);
+        value
+    }
+
+    /// Put a value taken out of its slot back, making its key valid again.
+    fn put_back(&mut self, key: Key<Provenance>, value: Value) {
+        let slot = &mut self.slots[key.index];
+        *slot = Slot {
+            generation: key.generation,
+            owned: false,
+            value: Some(value),
+        };
+
+        if let Some(position) = self.vacant.iter().rposition(|&index| index == key.index) {
+            self.vacant.swap_remove(position);
+        }
+        self.order.insert(key.generation, key.index);
+    }
+
+    /// Only keep the values for which the predicate returns `true`.
+    /// The values are visited in the same order as by [iter_mut](SeparateProvenanceMap::iter_mut).
+    ///
+    /// Values inserted with [insert_owned](SeparateProvenanceMap::insert_owned) are
+    /// always kept, and are not given to the predicate.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// let two = map.insert(2);
+    /// let three = map.insert(3);
+    ///
+    /// map.retain(|_, val| *val % 2 == 1);
+    ///
+    /// assert_eq!(Some(&1), map.try_get(one));
+    /// assert_eq!(None, map.try_get(two));
+    /// assert_eq!(Some(&3), map.try_get(three));
+    /// ```
+    pub fn retain<P: FnMut(Key<Provenance>, &mut Value) -> bool>(&mut self, mut predicate: P) {
+        for (index, slot) in self.slots.iter_mut().enumerate().filter(|(_, slot)| !slot.owned) {
+            if let Some(value) = slot.value.as_mut() {
+                if !predicate(Key::new(index, slot.generation), value) {
+                    slot.value = None;
+                    self.vacant.push(index);
+                    self.order.remove(&slot.generation);
+                }
+            }
+        }
     }
 
     /// Get an [iterator](Iterator) over all keys in the map.
+    ///
+    /// The values inserted with [insert_owned](SeparateProvenanceMap::insert_owned)
+    /// are left out, as their keys only are handed out by [borrowing](OwnedKey::borrow)
+    /// their owned keys.
     /// ```
     /// use provenance::SeparateProvenanceMap;
     /// struct Provenance;
@@ -466,16 +1439,20 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
     ///
     /// map.insert(1);
     /// map.insert(2);
-    /// map.insert(3);
+    /// map.insert_owned(3);
     ///
-    /// assert_eq!(3, map.keys().count());
+    /// assert_eq!(2, map.keys().count());
     /// ```
-    pub fn keys(&self) -> impl Iterator<Item = Key<Provenance>> {
-        (0..self.elements.len())
-            .map(Key::new)
+    pub fn keys(&self) -> impl Iterator<Item = Key<Provenance>> + '_ {
+        self.iter_with_keys().map(|(key, _)| key)
     }
 
     /// Get an [iterator](Iterator) over immutable references to each value in the map.
+    ///
+    /// The values are visited in slot order. A value inserted after a removal takes
+    /// the place of the removed value, so slot order only follows the order the values
+    /// were inserted in until a value is removed. Use [range](SeparateProvenanceMap::range)
+    /// to visit values in insertion order.
     /// ```
     /// use provenance::SeparateProvenanceMap;
     /// struct Provenance;
@@ -488,7 +1465,7 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
     /// assert_eq!(6, map.iter().sum());
     /// ```
     pub fn iter(&self) -> impl Iterator<Item = &Value> {
-        self.elements.iter()
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
     }
 
     /// Get an [iterator](Iterator) over mutable references to each value in the map.
@@ -507,10 +1484,10 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
     /// assert_eq!(9, map.iter().sum());
     /// ```
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Value> {
-        self.elements.iter_mut()
+        self.slots.iter_mut().filter_map(|slot| slot.value.as_mut())
     }
 
-    /// Search the map in insertion order for the first value that satisfy the given predicate.
+    /// Search the map in [slot order](SeparateProvenanceMap::iter) for the first value that satisfy the given predicate.
     /// If such value is found, an immutable reference to it is returned,
     /// ```
     /// use provenance::SeparateProvenanceMap;
@@ -536,7 +1513,7 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
     /// assert_eq!(None, map.find(|&val| val == 53));
     /// ```
     pub fn find<P: Fn(&Value) -> bool>(&self, predicate: P) -> Option<&Value> {
-        for value in self.elements.iter() {
+        for value in self.iter() {
             if predicate(value) {
                 return Some(value)
             }
@@ -545,7 +1522,7 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
         None
     }
 
-    /// Search the map in insertion order for the first value that satisfy the given predicate.
+    /// Search the map in [slot order](SeparateProvenanceMap::iter) for the first value that satisfy the given predicate.
     /// If such value is found, a mutable reference to it is returned,
     /// ```
     /// use provenance::SeparateProvenanceMap;
@@ -571,7 +1548,7 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
     /// assert_eq!(None, map.find_mut(|&val| val == 53));
     /// ```
     pub fn find_mut<P: Fn(&Value) -> bool>(&mut self, predicate: P) -> Option<&mut Value> {
-        for value in self.elements.iter_mut() {
+        for value in self.iter_mut() {
             if predicate(value) {
                 return Some(value)
             }
@@ -579,10 +1556,370 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
 
         None
     }
+
+    /// Get an [iterator](Iterator) over the keys of the map together with immutable
+    /// references to their values.
+    ///
+    /// Like [keys](SeparateProvenanceMap::keys), the values inserted with
+    /// [insert_owned](SeparateProvenanceMap::insert_owned) are left out.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// let two = map.insert(2);
+    /// map.insert_owned(3);
+    ///
+    /// let pairs: Vec<_> = map.iter_with_keys().collect();
+    /// assert_eq!(vec![(one, &1), (two, &2)], pairs);
+    /// ```
+    pub fn iter_with_keys(&self) -> impl Iterator<Item = (Key<Provenance>, &Value)> {
+        self.slots.iter()
+            .enumerate()
+            .filter(|(_, slot)| !slot.owned)
+            .filter_map(|(index, slot)| {
+                slot.value.as_ref().map(|value| (Key::new(index, slot.generation), value))
+            })
+    }
+
+    /// Get an [iterator](Iterator) over the keys of the map together with mutable
+    /// references to their values.
+    ///
+    /// Like [keys](SeparateProvenanceMap::keys), the values inserted with
+    /// [insert_owned](SeparateProvenanceMap::insert_owned) are left out.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// let two = map.insert(2);
+    ///
+    /// // Add ten to the value of `one` only
+    /// for (key, val) in map.iter_mut_with_keys() {
+    ///     if key == one {
+    ///         *val += 10;
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(&11, map.get(one));
+    /// assert_eq!(&2, map.get(two));
+    /// ```
+    pub fn iter_mut_with_keys(&mut self) -> impl Iterator<Item = (Key<Provenance>, &mut Value)> {
+        self.slots.iter_mut()
+            .enumerate()
+            .filter(|(_, slot)| !slot.owned)
+            .filter_map(|(index, slot)| {
+                let key = Key::new(index, slot.generation);
+                slot.value.as_mut().map(|value| (key, value))
+            })
+    }
+
+    /// Like [iter_with_keys](SeparateProvenanceMap::iter_with_keys), but including
+    /// the values inserted with [insert_owned](SeparateProvenanceMap::insert_owned).
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (Key<Provenance>, &Value)> {
+        self.slots.iter()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                slot.value.as_ref().map(|value| (Key::new(index, slot.generation), value))
+            })
+    }
+
+    /// Like [iter_mut_with_keys](SeparateProvenanceMap::iter_mut_with_keys), but
+    /// including the values inserted with [insert_owned](SeparateProvenanceMap::insert_owned).
+    pub(crate) fn entries_mut(&mut self) -> impl Iterator<Item = (Key<Provenance>, &mut Value)> {
+        self.slots.iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let key = Key::new(index, slot.generation);
+                slot.value.as_mut().map(|value| (key, value))
+            })
+    }
+
+    /// Search the map in [slot order](SeparateProvenanceMap::iter) for the first value that satisfy the given predicate.
+    /// If such value is found, its key is returned,
+    /// otherwise [`None`](std::option::Option::None) is returned.
+    ///
+    /// Only the values [iterated with their keys](SeparateProvenanceMap::iter_with_keys)
+    /// are searched, which leaves out those inserted with [insert_owned](SeparateProvenanceMap::insert_owned).
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// let two = map.insert(2);
+    ///
+    /// assert_eq!(Some(two), map.find_key(|&val| val == 2));
+    /// assert_eq!(None, map.find_key(|&val| val == 53));
+    /// ```
+    pub fn find_key<P: Fn(&Value) -> bool>(&self, predicate: P) -> Option<Key<Provenance>> {
+        self.iter_with_keys()
+            .find(|(_, value)| predicate(value))
+            .map(|(key, _)| key)
+    }
+
+    /// Search the map in [slot order](SeparateProvenanceMap::iter) for the first value that satisfy the given predicate.
+    /// If such value is found, its position among the values [iterated](Self::iter) over is returned,
+    /// otherwise [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.insert(3);
+    ///
+    /// assert_eq!(Some(1), map.position(|&val| val == 2));
+    /// assert_eq!(None, map.position(|&val| val == 53));
+    /// ```
+    pub fn position<P: Fn(&Value) -> bool>(&self, predicate: P) -> Option<usize> {
+        self.iter().position(predicate)
+    }
+
+    /// Get an [iterator](Iterator) over the keys of every value that satisfy the
+    /// given predicate, in [slot order](SeparateProvenanceMap::iter). The values inserted with
+    /// [insert_owned](SeparateProvenanceMap::insert_owned) are not considered.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// map.insert(2);
+    /// let three = map.insert(3);
+    ///
+    /// let odd: Vec<_> = map.filter_keys(|&val| val % 2 == 1).collect();
+    /// assert_eq!(vec![one, three], odd);
+    /// ```
+    pub fn filter_keys<'a, P: Fn(&Value) -> bool + 'a>(&'a self, predicate: P) -> impl Iterator<Item = Key<Provenance>> + 'a {
+        self.iter_with_keys()
+            .filter(move |(_, value)| predicate(value))
+            .map(|(key, _)| key)
+    }
+
+    /// Get an [iterator](Iterator) over the keys within a range, together with immutable
+    /// references to their values. The keys are visited in [order](Key#ordering), i.e.
+    /// in the order their values were inserted.
+    ///
+    /// Like [keys](SeparateProvenanceMap::keys), the values inserted with
+    /// [insert_owned](SeparateProvenanceMap::insert_owned) are left out.
+    ///
+    /// The map keeps its values in insertion order as well, so the start of the range
+    /// is found in logarithmic time, without visiting the values before it.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// let two = map.insert(2);
+    /// let three = map.insert(3);
+    ///
+    /// let values: Vec<_> = map.range(one..three).map(|(_, val)| *val).collect();
+    /// assert_eq!(vec![1, 2], values);
+    /// ```
+    pub fn range<R: RangeBounds<Key<Provenance>>>(&self, range: R) -> impl Iterator<Item = (Key<Provenance>, &Value)> {
+        // Keys are ordered by their generations alone, as no two values share one.
+        let generation = |bound: Bound<&Key<Provenance>>| match bound {
+            Bound::Included(key) => Bound::Included(key.generation),
+            Bound::Excluded(key) => Bound::Excluded(key.generation),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = generation(range.end_bound());
+
+        // Ranging to the end bound too would panic if it were before the start bound.
+        self.order.range((generation(range.start_bound()), Bound::Unbounded))
+            .take_while(move |(generation, _)| (Bound::Unbounded, end).contains(*generation))
+            .map(move |(&generation, &index)| (Key::new(index, generation), &self.slots[index]))
+            .filter(|(_, slot)| !slot.owned)
+            .filter_map(|(key, slot)| slot.value.as_ref().map(|value| (key, value)))
+    }
+
+    /// Get an [iterator](Iterator) over the keys of the values inserted after the value
+    /// of a key, in the order they were inserted.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// let checkpoint = map.insert(2);
+    /// let three = map.insert(3);
+    /// let four = map.insert(4);
+    ///
+    /// assert_eq!(vec![three, four], map.keys_after(checkpoint).collect::<Vec<_>>());
+    /// ```
+    pub fn keys_after(&self, key: Key<Provenance>) -> impl Iterator<Item = Key<Provenance>> + '_ {
+        self.range((Bound::Excluded(key), Bound::Unbounded))
+            .map(|(key, _)| key)
+    }
+
+    /// Get the key of the value that were inserted first among the values in the map,
+    /// not counting those inserted with [insert_owned](SeparateProvenanceMap::insert_owned).
+    /// If there is no such value, [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// assert_eq!(None, map.first_key());
+    ///
+    /// let one = map.insert(1);
+    /// map.insert(2);
+    /// assert_eq!(Some(one), map.first_key());
+    /// ```
+    pub fn first_key(&self) -> Option<Key<Provenance>> {
+        self.range(..).map(|(key, _)| key).next()
+    }
+
+    /// Get the key of the value that were inserted last among the values in the map,
+    /// not counting those inserted with [insert_owned](SeparateProvenanceMap::insert_owned).
+    /// If there is no such value, [`None`](std::option::Option::None) is returned.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// assert_eq!(None, map.last_key());
+    ///
+    /// map.insert(1);
+    /// let two = map.insert(2);
+    /// assert_eq!(Some(two), map.last_key());
+    /// ```
+    pub fn last_key(&self) -> Option<Key<Provenance>> {
+        self.order.iter()
+            .rev()
+            .map(|(&generation, &index)| Key::new(index, generation))
+            .find(|key| !self.slots[key.index].owned)
+    }
+}
+
+impl<Value: 'static> Index<Key<Value>> for ProvenanceMap<Value> {
+    type Output = Value;
+
+    /// Retrieve a value like [get](ProvenanceMap::get).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// assert_eq!(5, map[key]);
+    /// ```
+    fn index(&self, key: Key<Value>) -> &Value {
+        self.get(key)
+    }
+}
+
+impl<Value: 'static> IndexMut<Key<Value>> for ProvenanceMap<Value> {
+
+    /// Retrieve a value like [get_mut](ProvenanceMap::get_mut).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// map[key] += 1;
+    /// assert_eq!(6, map[key]);
+    /// ```
+    fn index_mut(&mut self, key: Key<Value>) -> &mut Value {
+        self.get_mut(key)
+    }
+}
+
+impl<Value: 'static> Extend<Value> for ProvenanceMap<Value> {
+
+    /// Insert each value of an [iterator](IntoIterator) into the map.
+    /// See [extend_keys](ProvenanceMap::extend_keys) to also get their keys.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// map.extend(vec![1, 2, 3]);
+    /// assert_eq!(6, map.iter().sum());
+    /// ```
+    fn extend<I: IntoIterator<Item = Value>>(&mut self, values: I) {
+        self.map.extend(values)
+    }
+}
+
+impl<Value: Debug + 'static> Debug for ProvenanceMap<Value> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.map.fmt(f)
+    }
 }
 
-/// A lightweight key referencing a value stored in a [ProvenanceMap](ProvenanceMap) or
-/// [SeparateProvenanceMap](SeparateProvenanceMap).
+impl<Provenance: 'static, Value: 'static> Index<Key<Provenance>> for SeparateProvenanceMap<Provenance, Value> {
+    type Output = Value;
+
+    /// Retrieve a value like [get](SeparateProvenanceMap::get).
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// assert_eq!(5, map[key]);
+    /// ```
+    fn index(&self, key: Key<Provenance>) -> &Value {
+        self.get(key)
+    }
+}
+
+impl<Provenance: 'static, Value: 'static> IndexMut<Key<Provenance>> for SeparateProvenanceMap<Provenance, Value> {
+
+    /// Retrieve a value like [get_mut](SeparateProvenanceMap::get_mut).
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// map[key] += 1;
+    /// assert_eq!(6, map[key]);
+    /// ```
+    fn index_mut(&mut self, key: Key<Provenance>) -> &mut Value {
+        self.get_mut(key)
+    }
+}
+
+impl<Provenance: 'static, Value: 'static> Extend<Value> for SeparateProvenanceMap<Provenance, Value> {
+
+    /// Insert each value of an [iterator](IntoIterator) into the map.
+    /// See [extend_keys](SeparateProvenanceMap::extend_keys) to also get their keys.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.extend(vec![1, 2, 3]);
+    /// assert_eq!(6, map.iter().sum());
+    /// ```
+    fn extend<I: IntoIterator<Item = Value>>(&mut self, values: I) {
+        for value in values {
+            self.insert(value);
+        }
+    }
+}
+
+impl<Provenance: 'static, Value: Debug + 'static> Debug for SeparateProvenanceMap<Provenance, Value> {
+
+    /// Format the map as its keys and their values.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// assert_eq!(format!("{{{:?}: 5}}", key), format!("{:?}", map));
+    /// ```
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.entries()).finish()
+    }
+}
+
+/// A lightweight key referencing a value stored in a [ProvenanceMap](ProvenanceMap),
+/// [SeparateProvenanceMap](SeparateProvenanceMap) or [BrandedMap](BrandedMap).
 ///
 /// Can only be created by methods on such map and thus will always be valid
 /// for the map that created it. Further, the map that creates the key "tags"
@@ -590,9 +1927,51 @@ impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Valu
 /// provenance, it is guaranteed that if a key match the required type signature
 /// for retrieving a value from a map, then that key were created by that map and
 /// reference a value in that map.
+///
+/// A key also carries a generation that is unique to the insertion that created it.
+/// Thus, a key whose value has been removed, or that has outlived its map, is never
+/// mistaken for the key of a value that later takes the place of its value.
+///
+/// # Ordering
+/// Keys are ordered by when their values were inserted, such that a key is less than
+/// the keys of values inserted after its value. This holds even when the value of a
+/// key takes the place of a removed value. Keys may thus be used in ordered collections,
+/// such as [BTreeMap](std::collections::BTreeMap), to keep values in insertion order.
+/// ```
+/// use provenance::SeparateProvenanceMap;
+/// use std::collections::BTreeSet;
+///
+/// struct Provenance;
+/// let mut map = SeparateProvenanceMap::<Provenance, &str>::new().unwrap();
+///
+/// let first = map.insert("first");
+/// let second = map.insert("second");
+/// map.remove(first);
+/// let third = map.insert("third"); // Takes the place of "first"
+///
+/// assert!(second < third);
+///
+/// let keys: BTreeSet<_> = vec![third, second].into_iter().collect();
+/// assert_eq!(vec![second, third], keys.into_iter().collect::<Vec<_>>());
+/// ```
+///
+/// # Thread safety
+/// A key only consists of an index, so it is [Send](Send) and [Sync](Sync)
+/// regardless of its provenance. This allows keys to be sent to other threads.
+/// ```
+/// use provenance::{Key, OwnedKey};
+/// use std::{cell::Cell, rc::Rc};
+///
+/// fn assert_send_sync<T: Send + Sync>() {}
+///
+/// assert_send_sync::<Key<Rc<()>>>();
+/// assert_send_sync::<Key<Cell<i32>>>();
+/// assert_send_sync::<OwnedKey<Rc<()>>>();
+/// ```
 pub struct Key<Provenance> {
     index: usize,
-    _pd: PhantomData<*const Provenance>,
+    generation: u64,
+    _pd: PhantomData<fn() -> Provenance>,
 }
 
 impl<Provenance> Key<Provenance> {
@@ -600,19 +1979,128 @@ impl<Provenance> Key<Provenance> {
     ///
     /// Deliberately non-pub, since it should be created by calling methods
     /// on maps, which guarantee that the key is valid.
-    fn new(index: usize) -> Self {
+    fn new(index: usize, generation: u64) -> Self {
         Key {
             index,
+            generation,
             _pd: Default::default()
         }
     }
 }
 
+/// A key that owns the value it references, in the sense that only it may remove that value.
+///
+/// Created by [insert_owned](SeparateProvenanceMap::insert_owned). An owned key can
+/// neither be copied nor cloned, and removing its value with
+/// [remove_owned](SeparateProvenanceMap::remove_owned) consumes it. Hence, the value
+/// is guaranteed to remain in the map for as long as the owned key exists.
+///
+/// The value is read and mutated through the owned key with
+/// [get_owned](SeparateProvenanceMap::get_owned) and
+/// [get_owned_mut](SeparateProvenanceMap::get_owned_mut), which only accept the
+/// owned key of the map that created it. Where a plain [Key](Key) is needed, the
+/// owned key may be [borrowed](OwnedKey::borrow) as one, and the value can not be
+/// removed while such borrow is alive.
+///
+/// Dropping an owned key leaves its value in the map, where it stays until the map is dropped.
+pub struct OwnedKey<Provenance> {
+    key: Key<Provenance>,
+    /// Identity of the map the key were created by.
+    instance: u64,
+}
+
+impl<Provenance> OwnedKey<Provenance> {
+    /// Borrow the owned key as a plain [Key](Key).
+    ///
+    /// The plain key is only good for reading and mutating the value, since
+    /// [remove](SeparateProvenanceMap::remove) refuses it like any key of an owned value.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// let key = owned.borrow();
+    ///
+    /// *map.get_mut(*key) += 1;
+    /// assert_eq!(&6, map.get(*key));
+    /// ```
+    ///
+    /// The value can not be removed while the owned key is borrowed:
+    /// ```compile_fail
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// let key = owned.borrow();
+    ///
+    /// map.remove_owned(owned);
+    /// map.get(*key); // The borrow of `owned` is still alive
+    /// ```
+    pub fn borrow(&self) -> BorrowedKey<'_, Provenance> {
+        BorrowedKey {
+            key: self.key,
+            _owner: Default::default(),
+        }
+    }
+}
+
+/// A plain [Key](Key) borrowed from an [OwnedKey](OwnedKey).
+///
+/// The owned key can not remove its value while the borrow is alive, so the
+/// key is guaranteed to be valid for at least as long.
+pub struct BorrowedKey<'a, Provenance> {
+    key: Key<Provenance>,
+    _owner: PhantomData<&'a OwnedKey<Provenance>>,
+}
+
+impl<'a, Provenance> Deref for BorrowedKey<'a, Provenance> {
+    type Target = Key<Provenance>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.key
+    }
+}
+
 // Deriving traits for Key has proved unreliable, hence they are manually implemented.
 
 impl<Provenance> Debug for Key<Provenance> {
+
+    /// Format the key as its index and generation, such that the key of a removed
+    /// value is told apart from the key of a value that took its place.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let old = map.insert(1);
+    /// map.remove(old);
+    /// let new = map.insert(2); // Takes the place of 1
+    ///
+    /// assert!(format!("{:?}", new).starts_with("MapKey { index: 0, generation: "));
+    /// assert_ne!(format!("{:?}", old), format!("{:?}", new));
+    /// ```
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MapKey")
+            .field("index", &self.index)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+impl<Provenance> Debug for OwnedKey<Provenance> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OwnedMapKey")
+            .field("index", &self.key.index)
+            .field("generation", &self.key.generation)
+            .finish()
+    }
+}
+
+impl<'a, Provenance> Debug for BorrowedKey<'a, Provenance> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "MapKey({})", self.index)
+        self.key.fmt(f)
     }
 }
 
@@ -620,10 +2108,7 @@ impl<Provenance> Debug for Key<Provenance> {
 
 impl<Provenance> Clone for Key<Provenance> {
     fn clone(&self) -> Self {
-        Key {
-            index: self.index,
-            _pd: Default::default(),
-        }
+        *self
     }
 }
 
@@ -633,7 +2118,7 @@ impl<Provenance> Copy for Key<Provenance> {}
 
 impl<Provenance> PartialEq for Key<Provenance> {
     fn eq(&self, other: &Self) -> bool {
-        self.index == other.index
+        self.index == other.index && self.generation == other.generation
     }
 }
 
@@ -643,6 +2128,24 @@ impl<Provenance> Eq for Key<Provenance> {}
 
 impl<Provenance> Hash for Key<Provenance> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.index.hash(state)
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+// Implemented manually to not require `Provenance: Ord`.
+impl<Provenance> PartialOrd for Key<Provenance> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Implemented manually to not require `Provenance: Ord`.
+impl<Provenance> Ord for Key<Provenance> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Generations increase with every insertion, except in maps where values
+        // never are removed. Those share a generation between all keys, but only
+        // ever increase the index.
+        (self.generation, self.index).cmp(&(other.generation, other.index))
     }
 }
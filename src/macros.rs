@@ -0,0 +1,130 @@
+//! Macros to declare maps without writing marker types by hand.
+
+/// Declare a map type that is its own provenance, together with an alias for its keys
+/// and, optionally, a singleton of the map.
+///
+/// The declared type wraps a [SeparateProvenanceMap](crate::SeparateProvenanceMap)
+/// with the declared type itself as provenance, so no separate marker type is needed.
+/// It dereferences to the wrapped map, and is created with `new` or `try_new`, which
+/// behave like [SeparateProvenanceMap::new](crate::SeparateProvenanceMap::new) and
+/// [SeparateProvenanceMap::try_new](crate::SeparateProvenanceMap::try_new).
+/// ```
+/// use provenance::provenance;
+///
+/// pub struct Currency { name: String }
+///
+/// provenance! {
+///     /// Every currency known to the application.
+///     pub struct Currencies(Map<Currency>);
+///     pub type CurrencyKey;
+/// }
+///
+/// #[derive(Copy, Clone)]
+/// struct Money { amount: i32, currency: CurrencyKey }
+///
+/// let mut currencies = Currencies::new().unwrap();
+/// let sek = currencies.insert(Currency { name: "Swedish Krona".into() });
+///
+/// let money = Money { amount: 5, currency: sek };
+/// assert_eq!("Swedish Krona", currencies.get(money.currency).name);
+///
+/// // Only one map may exist at a time
+/// assert!(Currencies::new().is_none());
+/// ```
+///
+/// A singleton of the map may be declared as well, as a `static`
+/// [GlobalProvenanceMap](crate::GlobalProvenanceMap) with the declared type as provenance.
+/// Its keys are of the same type as those of the declared map, but since the two share
+/// the provenance, only one of them may be used at a time. As the singleton is shared
+/// between threads, its values must be [Send](Send) and [Sync](Sync).
+/// ```
+/// use provenance::provenance;
+///
+/// provenance! {
+///     pub struct Currencies(Map<&'static str>);
+///     pub type CurrencyKey;
+///     pub static CURRENCIES;
+/// }
+///
+/// let sek: CurrencyKey = CURRENCIES.insert("SEK");
+/// assert_eq!(&"SEK", CURRENCIES.get(sek));
+///
+/// // The singleton has claimed the provenance
+/// assert!(Currencies::new().is_none());
+/// ```
+///
+/// Keys of one declared map can not be used with another, even if both store the same type of value.
+/// ```compile_fail
+/// use provenance::provenance;
+///
+/// provenance! {
+///     struct Names(Map<String>);
+///     type NameKey;
+/// }
+///
+/// provenance! {
+///     struct Titles(Map<String>);
+///     type TitleKey;
+/// }
+///
+/// let mut names = Names::new().unwrap();
+/// let titles = Titles::new().unwrap();
+///
+/// let key = names.insert("Jesper".to_string());
+/// titles.get(key);
+/// ```
+#[macro_export]
+macro_rules! provenance {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident(Map<$value:ty>);
+        $(#[$key_meta:meta])*
+        $key_vis:vis type $key:ident;
+        $(
+            $(#[$static_meta:meta])*
+            $static_vis:vis static $static:ident;
+        )?
+    ) => {
+        $(#[$meta])*
+        $vis struct $name($crate::SeparateProvenanceMap<$name, $value>);
+
+        $(#[$key_meta])*
+        $key_vis type $key = $crate::Key<$name>;
+
+        $(
+            $(#[$static_meta])*
+            $static_vis static $static: $crate::GlobalProvenanceMap<$name, $value> = $crate::GlobalProvenanceMap::new();
+        )?
+
+        impl $name {
+            /// Create the map, if it does not already exist.
+            /// If it does, [`None`](std::option::Option::None) is returned.
+            #[allow(dead_code)]
+            $vis fn new() -> ::std::option::Option<$name> {
+                $crate::SeparateProvenanceMap::new().map($name)
+            }
+
+            /// Create the map, if it does not already exist.
+            /// If it does, or the map could not be created for another reason,
+            /// an error describing why is returned.
+            #[allow(dead_code)]
+            $vis fn try_new() -> ::std::result::Result<$name, $crate::ProvenanceError> {
+                $crate::SeparateProvenanceMap::try_new().map($name)
+            }
+        }
+
+        impl ::std::ops::Deref for $name {
+            type Target = $crate::SeparateProvenanceMap<$name, $value>;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl ::std::ops::DerefMut for $name {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+    };
+}
@@ -0,0 +1,514 @@
+//! A map that notifies subscribers of changes to its values.
+
+use std::{
+    cell::RefCell,
+    fmt::{Debug, Formatter},
+    ops::{Deref, DerefMut},
+    rc::{Rc, Weak},
+};
+use crate::{Key, OwnedKey, ProvenanceError, SeparateProvenanceMap};
+
+type Hook<Provenance, Value> = Rc<RefCell<dyn FnMut(Key<Provenance>, &Value)>>;
+
+/// The kind of change a hook is notified of.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Insert,
+    Modify,
+    Remove,
+}
+
+/// The hooks of a map, shared with the [subscriptions](Subscription) to it.
+struct Hooks<Provenance, Value> {
+    next_id: u64,
+    hooks: Vec<(u64, Event, Hook<Provenance, Value>)>,
+}
+
+/// A [SeparateProvenanceMap](SeparateProvenanceMap) that calls hooks when values are
+/// inserted, modified or removed.
+///
+/// Hooks are registered with [on_insert](ObservableProvenanceMap::on_insert),
+/// [on_modify](ObservableProvenanceMap::on_modify) and
+/// [on_remove](ObservableProvenanceMap::on_remove), each returning a
+/// [Subscription](Subscription) that unregisters the hook when dropped. Hooks are given
+/// the key and value that changed, so dependent indices and caches may be kept up to date.
+///
+/// Hooks are not called for reading values, which is done through the underlying
+/// [SeparateProvenanceMap](SeparateProvenanceMap) the map dereferences to.
+/// ```
+/// use provenance::ObservableProvenanceMap;
+/// use std::{cell::RefCell, collections::HashMap, rc::Rc};
+///
+/// struct Users;
+/// let mut users = ObservableProvenanceMap::<Users, String>::new().unwrap();
+///
+/// // An index from name to key, kept up to date by hooks
+/// let by_name = Rc::new(RefCell::new(HashMap::new()));
+/// let index = by_name.clone();
+/// let _inserts = users.on_insert(move |key, name: &String| {
+///     index.borrow_mut().insert(name.clone(), key);
+/// });
+/// let index = by_name.clone();
+/// let _removals = users.on_remove(move |_, name: &String| {
+///     index.borrow_mut().remove(name);
+/// });
+///
+/// let alice = users.insert("Alice".to_string());
+/// users.insert("Bob".to_string());
+/// users.remove(alice);
+///
+/// assert_eq!(None, by_name.borrow().get("Alice"));
+/// assert_eq!("Bob", users.get(by_name.borrow()["Bob"]));
+/// ```
+pub struct ObservableProvenanceMap<Provenance, Value> {
+    map: SeparateProvenanceMap<Provenance, Value>,
+    hooks: Rc<RefCell<Hooks<Provenance, Value>>>,
+}
+
+/// A hook registered with an [ObservableProvenanceMap](ObservableProvenanceMap), which
+/// is unregistered when the subscription is dropped.
+///
+/// The subscription does not borrow the map, and may outlive it.
+#[must_use = "the hook is unregistered as soon as the subscription is dropped"]
+pub struct Subscription<Provenance, Value> {
+    hooks: Weak<RefCell<Hooks<Provenance, Value>>>,
+    id: u64,
+}
+
+/// A mutable reference to a value of an [ObservableProvenanceMap](ObservableProvenanceMap),
+/// which calls the [modify hooks](ObservableProvenanceMap::on_modify) of the map when dropped.
+pub struct ObservedMut<'a, Provenance, Value> {
+    key: Key<Provenance>,
+    value: &'a mut Value,
+    hooks: &'a Rc<RefCell<Hooks<Provenance, Value>>>,
+}
+
+impl<Provenance: 'static, Value: 'static> ObservableProvenanceMap<Provenance, Value> {
+
+    /// Creates a new empty map with some type as provenance, without any hooks.
+    ///
+    /// The provenance is claimed like by [SeparateProvenanceMap::new](SeparateProvenanceMap::new),
+    /// so [`None`](std::option::Option::None) is returned if any map with such provenance exists.
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// struct Provenance;
+    ///
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// map.insert(1); // Nobody is notified
+    ///
+    /// assert!(ObservableProvenanceMap::<Provenance, i32>::new().is_none());
+    /// ```
+    pub fn new() -> Option<ObservableProvenanceMap<Provenance, Value>> {
+        ObservableProvenanceMap::try_new().ok()
+    }
+
+    /// Creates a new empty map with some type as provenance, without any hooks,
+    /// or an [error](ProvenanceError) of why it could not be created.
+    /// See [SeparateProvenanceMap::try_new](SeparateProvenanceMap::try_new).
+    /// ```
+    /// use provenance::{ObservableProvenanceMap, ProvenanceError, SeparateProvenanceMap};
+    /// struct Provenance;
+    ///
+    /// let plain = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// let observable = ObservableProvenanceMap::<Provenance, i32>::try_new();
+    /// assert!(matches!(observable, Err(ProvenanceError::InUse { .. })));
+    ///
+    /// drop(plain);
+    /// assert!(ObservableProvenanceMap::<Provenance, i32>::try_new().is_ok());
+    /// ```
+    pub fn try_new() -> Result<ObservableProvenanceMap<Provenance, Value>, ProvenanceError> {
+        Ok(ObservableProvenanceMap {
+            map: SeparateProvenanceMap::try_new()?,
+            hooks: Rc::new(RefCell::new(Hooks { next_id: 0, hooks: vec![] })),
+        })
+    }
+
+    /// Register a hook that is called with the key and value of every value inserted
+    /// into the map, until the returned [subscription](Subscription) is dropped.
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// use std::{cell::Cell, rc::Rc};
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let sum = Rc::new(Cell::new(0));
+    /// let total = sum.clone();
+    /// let subscription = map.on_insert(move |_, val| total.set(total.get() + val));
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// drop(subscription);
+    /// map.insert(3);
+    ///
+    /// assert_eq!(3, sum.get());
+    /// ```
+    pub fn on_insert<F>(&self, hook: F) -> Subscription<Provenance, Value>
+        where F: FnMut(Key<Provenance>, &Value) + 'static
+    {
+        self.subscribe(Event::Insert, Rc::new(RefCell::new(hook)))
+    }
+
+    /// Register a hook that is called with the key and value of every value modified
+    /// in the map, until the returned [subscription](Subscription) is dropped.
+    ///
+    /// Values retrieved mutably are considered modified, whether they were changed or
+    /// not. The hook is called once the [reference](ObservedMut) to the value is dropped.
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// use std::{cell::RefCell, rc::Rc};
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let modified = Rc::new(RefCell::new(vec![]));
+    /// let log = modified.clone();
+    /// let _subscription = map.on_modify(move |key, &val| log.borrow_mut().push((key, val)));
+    ///
+    /// let key = map.insert(1);
+    /// *map.get_mut(key) += 1;
+    ///
+    /// assert_eq!(vec![(key, 2)], *modified.borrow());
+    /// ```
+    pub fn on_modify<F>(&self, hook: F) -> Subscription<Provenance, Value>
+        where F: FnMut(Key<Provenance>, &Value) + 'static
+    {
+        self.subscribe(Event::Modify, Rc::new(RefCell::new(hook)))
+    }
+
+    /// Register a hook that is called with the key and value of every value removed
+    /// from the map, until the returned [subscription](Subscription) is dropped.
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// use std::{cell::RefCell, rc::Rc};
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let removed = Rc::new(RefCell::new(vec![]));
+    /// let log = removed.clone();
+    /// let _subscription = map.on_remove(move |key, &val| log.borrow_mut().push((key, val)));
+    ///
+    /// let key = map.insert(1);
+    /// map.remove(key);
+    ///
+    /// assert_eq!(vec![(key, 1)], *removed.borrow());
+    /// ```
+    pub fn on_remove<F>(&self, hook: F) -> Subscription<Provenance, Value>
+        where F: FnMut(Key<Provenance>, &Value) + 'static
+    {
+        self.subscribe(Event::Remove, Rc::new(RefCell::new(hook)))
+    }
+
+    /// Insert a value into this map, calling the [insert hooks](ObservableProvenanceMap::on_insert).
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    pub fn insert(&mut self, value: Value) -> Key<Provenance> {
+        let key = self.map.insert(value);
+        notify(&self.hooks, Event::Insert, key, self.map.get(key));
+        key
+    }
+
+    /// Insert a value into this map, and get an [owned key](OwnedKey) to it,
+    /// calling the [insert hooks](ObservableProvenanceMap::on_insert).
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// assert_eq!(&5, map.get_owned(&owned));
+    /// ```
+    pub fn insert_owned(&mut self, value: Value) -> OwnedKey<Provenance> {
+        let owned = self.map.insert_owned(value);
+        notify(&self.hooks, Event::Insert, owned.key, self.map.get(owned.key));
+        owned
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to a stored value.
+    ///
+    /// The [modify hooks](ObservableProvenanceMap::on_modify) are called when the
+    /// returned reference is dropped.
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// *map.get_mut(key) += 1;
+    /// assert_eq!(&6, map.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value of the key has been removed, see [SeparateProvenanceMap::get_mut].
+    pub fn get_mut(&mut self, key: Key<Provenance>) -> ObservedMut<'_, Provenance, Value> {
+        ObservedMut {
+            key,
+            value: self.map.get_mut(key),
+            hooks: &self.hooks,
+        }
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to a stored value, if the
+    /// value still is in the map.
+    ///
+    /// The [modify hooks](ObservableProvenanceMap::on_modify) are called when the
+    /// returned reference is dropped.
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// map.remove(key);
+    ///
+    /// assert!(map.try_get_mut(key).is_none());
+    /// ```
+    pub fn try_get_mut(&mut self, key: Key<Provenance>) -> Option<ObservedMut<'_, Provenance, Value>> {
+        Some(ObservedMut {
+            key,
+            value: self.map.try_get_mut(key)?,
+            hooks: &self.hooks,
+        })
+    }
+
+    /// Use an [owned key](OwnedKey) to retrieve a mutable reference to its value.
+    ///
+    /// The [modify hooks](ObservableProvenanceMap::on_modify) are called when the
+    /// returned reference is dropped.
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// *map.get_owned_mut(&owned) += 1;
+    /// assert_eq!(&6, map.get_owned(&owned));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics like [SeparateProvenanceMap::get_owned_mut].
+    pub fn get_owned_mut(&mut self, key: &OwnedKey<Provenance>) -> ObservedMut<'_, Provenance, Value> {
+        ObservedMut {
+            key: key.key,
+            value: self.map.get_owned_mut(key),
+            hooks: &self.hooks,
+        }
+    }
+
+    /// Call a function with the key and a mutable reference to each value in the map,
+    /// calling the [modify hooks](ObservableProvenanceMap::on_modify) with every value
+    /// once the function has returned.
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// use std::{cell::Cell, rc::Rc};
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let count = Rc::new(Cell::new(0));
+    /// let counter = count.clone();
+    /// let _subscription = map.on_modify(move |_, _| counter.set(counter.get() + 1));
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.for_each_mut(|_, val| *val *= 10);
+    ///
+    /// assert_eq!(30, map.iter().sum());
+    /// assert_eq!(2, count.get());
+    /// ```
+    pub fn for_each_mut<F: FnMut(Key<Provenance>, &mut Value)>(&mut self, mut f: F) {
+        for (key, value) in self.map.entries_mut() {
+            f(key, value);
+            notify(&self.hooks, Event::Modify, key, value);
+        }
+    }
+
+    /// Remove a value from the map, calling the [remove hooks](ObservableProvenanceMap::on_remove).
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// assert_eq!(5, map.remove(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics like [SeparateProvenanceMap::remove].
+    pub fn remove(&mut self, key: Key<Provenance>) -> Value {
+        let value = self.map.remove(key);
+        notify(&self.hooks, Event::Remove, key, &value);
+        value
+    }
+
+    /// Remove a value inserted with [insert_owned](ObservableProvenanceMap::insert_owned),
+    /// calling the [remove hooks](ObservableProvenanceMap::on_remove).
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// assert_eq!(5, map.remove_owned(owned));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics like [SeparateProvenanceMap::remove_owned].
+    pub fn remove_owned(&mut self, key: OwnedKey<Provenance>) -> Value {
+        let removed = key.key;
+        let value = self.map.remove_owned(key);
+        notify(&self.hooks, Event::Remove, removed, &value);
+        value
+    }
+
+    /// Retain only the values that satisfy the given predicate, see
+    /// [SeparateProvenanceMap::retain](SeparateProvenanceMap::retain).
+    ///
+    /// The [remove hooks](ObservableProvenanceMap::on_remove) are called with each value
+    /// that is removed, and the [modify hooks](ObservableProvenanceMap::on_modify) with
+    /// each value that is kept, as the predicate may have changed it.
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// use std::{cell::RefCell, rc::Rc};
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let removed = Rc::new(RefCell::new(vec![]));
+    /// let log = removed.clone();
+    /// let _subscription = map.on_remove(move |_, &val| log.borrow_mut().push(val));
+    ///
+    /// map.extend(1..=4);
+    /// map.retain(|_, val| *val % 2 == 1);
+    ///
+    /// assert_eq!(vec![2, 4], *removed.borrow());
+    /// ```
+    pub fn retain<P: FnMut(Key<Provenance>, &mut Value) -> bool>(&mut self, mut predicate: P) {
+        let hooks = &self.hooks;
+        self.map.retain(|key, value| {
+            let keep = predicate(key, value);
+            let event = if keep { Event::Modify } else { Event::Remove };
+            notify(hooks, event, key, value);
+            keep
+        })
+    }
+
+    /// Insert every value of an iterator into the map, calling the
+    /// [insert hooks](ObservableProvenanceMap::on_insert) with each, and get their keys.
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let keys = map.extend_keys(vec![1, 2, 3]);
+    /// assert_eq!(&2, map.get(keys[1]));
+    /// ```
+    pub fn extend_keys<I: IntoIterator<Item = Value>>(&mut self, values: I) -> Vec<Key<Provenance>> {
+        values.into_iter()
+            .map(|value| self.insert(value))
+            .collect()
+    }
+
+    fn subscribe(&self, event: Event, hook: Hook<Provenance, Value>) -> Subscription<Provenance, Value> {
+        let mut hooks = self.hooks.borrow_mut();
+        let id = hooks.next_id;
+        hooks.next_id += 1;
+        hooks.hooks.push((id, event, hook));
+
+        Subscription {
+            hooks: Rc::downgrade(&self.hooks),
+            id,
+        }
+    }
+}
+
+/// Call every hook registered for an event.
+///
+/// Takes only the hooks, as an [ObservedMut](ObservedMut) notifies them when dropped,
+/// without access to the map it borrows the value from.
+fn notify<Provenance, Value>(
+    hooks: &RefCell<Hooks<Provenance, Value>>,
+    event: Event,
+    key: Key<Provenance>,
+    value: &Value,
+) {
+    // The hooks are collected before any is called, so that hooks may subscribe and
+    // unsubscribe without the registry being borrowed.
+    let called: Vec<_> = hooks.borrow().hooks.iter()
+        .filter(|(_, registered, _)| *registered == event)
+        .map(|(_, _, hook)| hook.clone())
+        .collect();
+
+    for hook in called {
+        (hook.borrow_mut())(key, value);
+    }
+}
+
+impl<Provenance, Value> Drop for Subscription<Provenance, Value> {
+    fn drop(&mut self) {
+        if let Some(hooks) = self.hooks.upgrade() {
+            hooks.borrow_mut().hooks.retain(|(id, _, _)| *id != self.id);
+        }
+    }
+}
+
+impl<'a, Provenance, Value> Deref for ObservedMut<'a, Provenance, Value> {
+    type Target = Value;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<'a, Provenance, Value> DerefMut for ObservedMut<'a, Provenance, Value> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+impl<'a, Provenance, Value> Drop for ObservedMut<'a, Provenance, Value> {
+    fn drop(&mut self) {
+        notify(self.hooks, Event::Modify, self.key, self.value);
+    }
+}
+
+impl<Provenance, Value> Deref for ObservableProvenanceMap<Provenance, Value> {
+    type Target = SeparateProvenanceMap<Provenance, Value>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<Provenance: 'static, Value: 'static> Extend<Value> for ObservableProvenanceMap<Provenance, Value> {
+
+    /// Insert every value of an iterator into the map, calling the
+    /// [insert hooks](ObservableProvenanceMap::on_insert) with each.
+    /// ```
+    /// use provenance::ObservableProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = ObservableProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.extend(vec![1, 2, 3]);
+    /// assert_eq!(3, map.len());
+    /// ```
+    fn extend<I: IntoIterator<Item = Value>>(&mut self, values: I) {
+        for value in values {
+            self.insert(value);
+        }
+    }
+}
+
+impl<Provenance: 'static, Value: Debug + 'static> Debug for ObservableProvenanceMap<Provenance, Value> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+impl<'a, Provenance, Value: Debug> Debug for ObservedMut<'a, Provenance, Value> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
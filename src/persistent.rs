@@ -0,0 +1,407 @@
+//! A map whose versions share structure, such that old versions remain usable.
+
+use std::{
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+    rc::Rc,
+};
+use crate::{registry::{self, Claim}, Key, ProvenanceError};
+
+const MISSING_VALUE: &str = "key refers to a value that is not in this version of the map";
+
+/// Number of bits of an index used to select a child at each level of the trie.
+const BITS: u32 = 5;
+/// Number of children of each node.
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+/// A map where inserting a value creates a new version of the map, and leaves the
+/// version inserted into as it were.
+///
+/// Versions share the parts of their storage that they have in common, so creating a
+/// version only copies a few small nodes. A key may be used with the version created
+/// by the insertion that created the key, and with every version derived from that one.
+/// ```
+/// use provenance::PersistentProvenanceMap;
+///
+/// struct History;
+/// let empty = PersistentProvenanceMap::<History, &str>::new().unwrap();
+///
+/// let (first, hello) = empty.insert("Hello");
+/// let (second, world) = first.insert("world");
+///
+/// assert_eq!("Hello world", format!("{} {}", second.get(hello), second.get(world)));
+///
+/// // Older versions are unchanged
+/// assert_eq!(1, first.len());
+/// assert_eq!(None, first.try_get(world));
+/// ```
+///
+/// Every version of a map shares its provenance, which is released once every version has been dropped.
+/// Values inserted into different versions get different keys, so a key never is mistaken for the key of
+/// a value in another version.
+/// ```
+/// use provenance::PersistentProvenanceMap;
+/// struct Provenance;
+/// let empty = PersistentProvenanceMap::<Provenance, i32>::new().unwrap();
+///
+/// let (left, one) = empty.insert(1);
+/// let (right, two) = empty.insert(2);
+///
+/// assert_eq!(None, right.try_get(one));
+/// assert_eq!(None, left.try_get(two));
+///
+/// assert!(PersistentProvenanceMap::<Provenance, i32>::new().is_none());
+/// drop((empty, left, right));
+/// assert!(PersistentProvenanceMap::<Provenance, i32>::new().is_some());
+/// ```
+pub struct PersistentProvenanceMap<Provenance, Value> {
+    root: Rc<Node<Value>>,
+    /// Number of levels of branches above the leaves.
+    depth: u32,
+    len: usize,
+    // Only held to release the provenance when every version of the map is dropped.
+    _claim: Rc<Claim>,
+    _pd: PhantomData<fn() -> Provenance>,
+}
+
+/// A node of the trie storing the values of a map.
+enum Node<Value> {
+    Branch(Vec<Rc<Node<Value>>>),
+    Leaf(Vec<Rc<Element<Value>>>),
+}
+
+/// A value, and the generation of the key created when it were inserted.
+struct Element<Value> {
+    generation: u64,
+    value: Value,
+}
+
+impl<Provenance: 'static, Value: 'static> PersistentProvenanceMap<Provenance, Value> {
+
+    /// Creates a new empty map with some type as provenance.
+    ///
+    /// If a map with such provenance already exists, [`None`](std::option::Option::None) will be returned.
+    /// ```
+    /// use provenance::PersistentProvenanceMap;
+    /// struct Provenance;
+    ///
+    /// let map = PersistentProvenanceMap::<Provenance, i32>::new();
+    /// assert!(map.is_some());
+    ///
+    /// let map = PersistentProvenanceMap::<Provenance, i32>::new();
+    /// assert!(map.is_none());
+    /// ```
+    pub fn new() -> Option<PersistentProvenanceMap<Provenance, Value>> {
+        PersistentProvenanceMap::try_new().ok()
+    }
+
+    /// Creates a new empty map with some type as provenance.
+    ///
+    /// If a map with such provenance already exists, or the map could not be created
+    /// for another reason, an [error](ProvenanceError) describing why is returned.
+    /// ```
+    /// use provenance::{PersistentProvenanceMap, ProvenanceError};
+    /// struct Provenance;
+    ///
+    /// let map = PersistentProvenanceMap::<Provenance, i32>::try_new();
+    /// assert!(map.is_ok());
+    ///
+    /// let map = PersistentProvenanceMap::<Provenance, i32>::try_new();
+    /// assert!(matches!(map, Err(ProvenanceError::InUse { .. })));
+    /// ```
+    pub fn try_new() -> Result<PersistentProvenanceMap<Provenance, Value>, ProvenanceError> {
+        Ok(PersistentProvenanceMap {
+            root: Rc::new(Node::Leaf(Vec::new())),
+            depth: 0,
+            len: 0,
+            _claim: Rc::new(Claim::acquire::<Provenance>()?),
+            _pd: Default::default(),
+        })
+    }
+
+    /// Insert a value, creating a new version of the map.
+    /// The new version is returned together with a unique key, which may be used to
+    /// retrieve the value from the new version and every version derived from it.
+    /// ```
+    /// use provenance::PersistentProvenanceMap;
+    /// struct Provenance;
+    /// let map = PersistentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let (map, key) = map.insert(5);
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    pub fn insert(&self, value: Value) -> (PersistentProvenanceMap<Provenance, Value>, Key<Provenance>) {
+        let index = self.len;
+        let element = Rc::new(Element {
+            generation: registry::fresh_generation(),
+            value,
+        });
+        let key = Key::new(index, element.generation);
+
+        let (root, depth) = if index == WIDTH << (BITS * self.depth) {
+            // The trie is full, so it is put below a new root.
+            let root = Node::Branch(vec![Rc::clone(&self.root), Rc::new(Node::path(self.depth, element))]);
+            (root, self.depth + 1)
+        } else {
+            (self.root.push(self.depth, index, element), self.depth)
+        };
+
+        (self.version(Rc::new(root), depth, index + 1), key)
+    }
+
+    /// Replace the value of a key, creating a new version of the map.
+    ///
+    /// The key remains valid for the new version, where it retrieves the new value,
+    /// while versions that already existed keep the old value.
+    /// ```
+    /// use provenance::PersistentProvenanceMap;
+    /// struct Provenance;
+    /// let map = PersistentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let (old, key) = map.insert(5);
+    /// let new = old.set(key, 6);
+    ///
+    /// assert_eq!(&5, old.get(key));
+    /// assert_eq!(&6, new.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value of the key is not in this version of the map.
+    pub fn set(&self, key: Key<Provenance>, value: Value) -> PersistentProvenanceMap<Provenance, Value> {
+        assert!(self.try_get(key).is_some(), "{}", MISSING_VALUE);
+
+        let element = Rc::new(Element {
+            generation: key.generation,
+            value,
+        });
+        let root = self.root.replace(self.depth, key.index, element);
+
+        self.version(Rc::new(root), self.depth, self.len)
+    }
+
+    /// Use a [key](Key) to retrieve an immutable reference to a stored value.
+    /// ```
+    /// use provenance::PersistentProvenanceMap;
+    /// struct Provenance;
+    /// let map = PersistentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let (map, key) = map.insert(5);
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value of the key is not in this version of the map, e.g. if the
+    /// version is older than the key, or derived from a version older than the key.
+    /// ```should_panic
+    /// use provenance::PersistentProvenanceMap;
+    /// struct Provenance;
+    /// let empty = PersistentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let (_, key) = empty.insert(5);
+    /// empty.get(key); // Panics since the value were inserted into a later version
+    /// ```
+    pub fn get(&self, key: Key<Provenance>) -> &Value {
+        self.try_get(key).expect(MISSING_VALUE)
+    }
+
+    /// Use a [key](Key) to retrieve an immutable reference to a stored value,
+    /// if the value is in this version of the map.
+    /// ```
+    /// use provenance::PersistentProvenanceMap;
+    /// struct Provenance;
+    /// let empty = PersistentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let (map, key) = empty.insert(5);
+    /// assert_eq!(Some(&5), map.try_get(key));
+    /// assert_eq!(None, empty.try_get(key));
+    /// ```
+    pub fn try_get(&self, key: Key<Provenance>) -> Option<&Value> {
+        self.element(key.index)
+            .filter(|element| element.generation == key.generation)
+            .map(|element| &element.value)
+    }
+
+    /// Get an [iterator](Iterator) over all keys in this version of the map.
+    /// ```
+    /// use provenance::PersistentProvenanceMap;
+    /// struct Provenance;
+    /// let map = PersistentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let (map, one) = map.insert(1);
+    /// let (map, two) = map.insert(2);
+    ///
+    /// assert_eq!(vec![one, two], map.keys().collect::<Vec<_>>());
+    /// ```
+    pub fn keys(&self) -> impl Iterator<Item = Key<Provenance>> + '_ {
+        self.elements()
+            .enumerate()
+            .map(|(index, element)| Key::new(index, element.generation))
+    }
+
+    /// Get an [iterator](Iterator) over immutable references to each value in this version of the map.
+    /// ```
+    /// use provenance::PersistentProvenanceMap;
+    /// struct Provenance;
+    /// let map = PersistentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let (map, _) = map.insert(1);
+    /// let (map, _) = map.insert(2);
+    /// let (map, _) = map.insert(3);
+    ///
+    /// assert_eq!(6, map.iter().sum());
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = &Value> {
+        self.elements().map(|element| &element.value)
+    }
+
+    /// Get the number of values in this version of the map.
+    /// ```
+    /// use provenance::PersistentProvenanceMap;
+    /// struct Provenance;
+    /// let empty = PersistentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let (map, _) = empty.insert(1);
+    /// assert_eq!(1, map.len());
+    /// assert_eq!(0, empty.len());
+    /// ```
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether this version of the map contains no values.
+    /// ```
+    /// use provenance::PersistentProvenanceMap;
+    /// struct Provenance;
+    /// let empty = PersistentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let (map, _) = empty.insert(1);
+    /// assert!(!map.is_empty());
+    /// assert!(empty.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Create a version with the given storage, sharing the provenance of this version.
+    fn version(&self, root: Rc<Node<Value>>, depth: u32, len: usize) -> PersistentProvenanceMap<Provenance, Value> {
+        PersistentProvenanceMap {
+            root,
+            depth,
+            len,
+            _claim: Rc::clone(&self._claim),
+            _pd: Default::default(),
+        }
+    }
+
+    /// The element at an index, if the index is within this version.
+    fn element(&self, index: usize) -> Option<&Element<Value>> {
+        if index >= self.len {
+            return None;
+        }
+
+        let mut node = &*self.root;
+        for level in (1..=self.depth).rev() {
+            match node {
+                Node::Branch(children) => node = &children[child_index(index, level)],
+                Node::Leaf(_) => unreachable!("leaves are only found at the bottom level"),
+            }
+        }
+
+        match node {
+            Node::Leaf(elements) => Some(&elements[index & MASK]),
+            Node::Branch(_) => unreachable!("branches are only found above the bottom level"),
+        }
+    }
+
+    /// Every element of this version, in insertion order.
+    fn elements(&self) -> impl Iterator<Item = &Element<Value>> {
+        (0..self.len).map(move |index| self.element(index).expect("index is within the version"))
+    }
+}
+
+impl<Value> Node<Value> {
+
+    /// A node holding only an element, at the given level.
+    fn path(level: u32, element: Rc<Element<Value>>) -> Node<Value> {
+        if level == 0 {
+            Node::Leaf(vec![element])
+        } else {
+            Node::Branch(vec![Rc::new(Node::path(level - 1, element))])
+        }
+    }
+
+    /// A copy of this node, at the given level, with an element added at the next free index.
+    fn push(&self, level: u32, index: usize, element: Rc<Element<Value>>) -> Node<Value> {
+        match self {
+            Node::Leaf(elements) => {
+                let mut elements = elements.clone();
+                elements.push(element);
+                Node::Leaf(elements)
+            }
+            Node::Branch(children) => {
+                let mut children = children.clone();
+                let child = child_index(index, level);
+
+                match children.get(child) {
+                    Some(node) => children[child] = Rc::new(node.push(level - 1, index, element)),
+                    None => children.push(Rc::new(Node::path(level - 1, element))),
+                }
+
+                Node::Branch(children)
+            }
+        }
+    }
+
+    /// A copy of this node, at the given level, with the element at an index replaced.
+    fn replace(&self, level: u32, index: usize, element: Rc<Element<Value>>) -> Node<Value> {
+        match self {
+            Node::Leaf(elements) => {
+                let mut elements = elements.clone();
+                elements[index & MASK] = element;
+                Node::Leaf(elements)
+            }
+            Node::Branch(children) => {
+                let mut children = children.clone();
+                let child = child_index(index, level);
+                children[child] = Rc::new(children[child].replace(level - 1, index, element));
+                Node::Branch(children)
+            }
+        }
+    }
+}
+
+/// The index of the child to follow, at the given level, to reach an index.
+fn child_index(index: usize, level: u32) -> usize {
+    (index >> (BITS * level)) & MASK
+}
+
+impl<Provenance, Value> Clone for PersistentProvenanceMap<Provenance, Value> {
+
+    /// Copy this version of the map. The copy shares storage and provenance with
+    /// this version, so keys valid for this version are valid for the copy.
+    /// ```
+    /// use provenance::PersistentProvenanceMap;
+    /// struct Provenance;
+    /// let map = PersistentProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let (map, key) = map.insert(5);
+    /// let copy = map.clone();
+    /// assert_eq!(&5, copy.get(key));
+    /// ```
+    fn clone(&self) -> Self {
+        PersistentProvenanceMap {
+            root: Rc::clone(&self.root),
+            depth: self.depth,
+            len: self.len,
+            _claim: Rc::clone(&self._claim),
+            _pd: Default::default(),
+        }
+    }
+}
+
+impl<Provenance: 'static, Value: Debug + 'static> Debug for PersistentProvenanceMap<Provenance, Value> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.keys().zip(self.iter())).finish()
+    }
+}
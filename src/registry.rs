@@ -0,0 +1,58 @@
+//! Bookkeeping of which types currently are in use as provenance.
+
+use std::{
+    any::TypeId,
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+};
+use lazy_static::lazy_static;
+use crate::ProvenanceError;
+
+lazy_static! {
+    static ref USED_PROVENANCE: Mutex<HashSet<TypeId>> = Mutex::new(Default::default());
+}
+
+/// Source of generations. Every inserted value get a generation that no other
+/// value in this process has had, which lets keys tell apart values that have
+/// occupied the same slot, even in different maps with the same provenance.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Draw a generation that never has been, and never will be, drawn again.
+pub(crate) fn fresh_generation() -> u64 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Exclusive right to use a type as provenance.
+///
+/// The right is returned to the registry when the claim is dropped.
+pub(crate) struct Claim {
+    type_id: TypeId,
+}
+
+impl Claim {
+    /// Claim `Provenance`, unless it already is claimed.
+    pub(crate) fn acquire<Provenance: 'static>() -> Result<Claim, ProvenanceError> {
+        let provenance = std::any::type_name::<Provenance>();
+        let type_id = TypeId::of::<Provenance>();
+        // The set is never left in an inconsistent state, as it only is changed by
+        // single insertions and removals, so a poisoned lock is safe to use.
+        let mut used_provenance = USED_PROVENANCE.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if used_provenance.insert(type_id) {
+            Ok(Claim { type_id })
+        } else {
+            Err(ProvenanceError::InUse { provenance })
+        }
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        // A poisoned lock is safe to use, see `acquire`.
+        let mut used_provenance = USED_PROVENANCE.lock().unwrap_or_else(PoisonError::into_inner);
+        used_provenance.remove(&self.type_id);
+    }
+}
@@ -0,0 +1,175 @@
+//! Joins of maps whose values refer to the values of other maps.
+
+use std::ops::Index;
+use crate::{Key, ProvenanceMap, SeparateProvenanceMap};
+
+/// A value that refers to a value of another map, through a [key](Key) of it.
+///
+/// Implementing the trait declares which key a value refers through, such that maps of
+/// such values may be [joined](SeparateProvenanceMap::join) with the map the keys belong to.
+/// A value may refer to several maps, by implementing the trait once for each.
+/// ```
+/// use provenance::{Key, ProvenanceMap, Relation};
+///
+/// struct Currency { name: &'static str }
+/// struct Money { amount: i32, currency: Key<Currency> }
+///
+/// impl Relation<Currency> for Money {
+///     fn foreign_key(&self) -> Key<Currency> {
+///         self.currency
+///     }
+/// }
+///
+/// let mut currencies = ProvenanceMap::<Currency>::new().unwrap();
+/// let sek = currencies.insert(Currency { name: "SEK" });
+///
+/// let mut wallet = ProvenanceMap::<Money>::new().unwrap();
+/// wallet.insert(Money { amount: 5, currency: sek });
+///
+/// for (_, money, currency) in wallet.join(&currencies) {
+///     assert_eq!("5 SEK", format!("{} {}", money.amount, currency.name));
+/// }
+/// ```
+pub trait Relation<Provenance> {
+    /// The key of the value referred to.
+    fn foreign_key(&self) -> Key<Provenance>;
+}
+
+impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Value> {
+
+    /// Get an [iterator](Iterator) over each value in the map together with its key,
+    /// and the value of another map it [refers to](Relation).
+    ///
+    /// The other map may be any map that can be [indexed](Index) by the keys referred
+    /// through, such as a [ProvenanceMap](ProvenanceMap) or a
+    /// [SeparateProvenanceMap](SeparateProvenanceMap).
+    /// ```
+    /// use provenance::{Key, Relation, SeparateProvenanceMap};
+    ///
+    /// struct Currencies;
+    /// struct Wallet;
+    /// struct Money { amount: i32, currency: Key<Currencies> }
+    ///
+    /// impl Relation<Currencies> for Money {
+    ///     fn foreign_key(&self) -> Key<Currencies> {
+    ///         self.currency
+    ///     }
+    /// }
+    ///
+    /// let mut currencies = SeparateProvenanceMap::<Currencies, &str>::new().unwrap();
+    /// let sek = currencies.insert("SEK");
+    /// let nok = currencies.insert("NOK");
+    ///
+    /// let mut wallet = SeparateProvenanceMap::<Wallet, Money>::new().unwrap();
+    /// wallet.insert(Money { amount: 5, currency: sek });
+    /// wallet.insert(Money { amount: 10, currency: nok });
+    ///
+    /// let amounts = wallet.join(&currencies)
+    ///     .map(|(_, money, currency)| format!("{} {}", money.amount, currency))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(vec!["5 SEK", "10 NOK"], amounts);
+    /// ```
+    ///
+    /// # Panics
+    /// The iterator panics if a value refers to a value that has been removed from
+    /// the other map, like [get](SeparateProvenanceMap::get).
+    pub fn join<'a, OtherProvenance: 'static, Other: 'a, M>(&'a self, other: &'a M) -> impl Iterator<Item = (Key<Provenance>, &'a Value, &'a Other)>
+        where Value: Relation<OtherProvenance>, M: Index<Key<OtherProvenance>, Output = Other>
+    {
+        self.join_by(other, Value::foreign_key)
+    }
+
+    /// Get an [iterator](Iterator) over each value in the map together with its key,
+    /// and the value of another map whose key is given by a function.
+    ///
+    /// Like [join](SeparateProvenanceMap::join), but without the values implementing
+    /// [Relation](Relation), e.g. for values that refer to the same map in several ways.
+    /// ```
+    /// use provenance::{Key, SeparateProvenanceMap};
+    ///
+    /// struct Currencies;
+    /// struct Rates;
+    /// struct Rate { from: Key<Currencies>, to: Key<Currencies>, rate: f64 }
+    ///
+    /// let mut currencies = SeparateProvenanceMap::<Currencies, &str>::new().unwrap();
+    /// let sek = currencies.insert("SEK");
+    /// let nok = currencies.insert("NOK");
+    ///
+    /// let mut rates = SeparateProvenanceMap::<Rates, Rate>::new().unwrap();
+    /// let key = rates.insert(Rate { from: sek, to: nok, rate: 1.0 });
+    ///
+    /// let (rate, _, to) = rates.join_by(&currencies, |rate| rate.to).next().unwrap();
+    /// assert_eq!((key, &"NOK"), (rate, to));
+    /// ```
+    ///
+    /// # Panics
+    /// The iterator panics if a value refers to a value that has been removed from
+    /// the other map, like [get](SeparateProvenanceMap::get).
+    pub fn join_by<'a, OtherProvenance: 'static, Other: 'a, M, F>(&'a self, other: &'a M, foreign_key: F) -> impl Iterator<Item = (Key<Provenance>, &'a Value, &'a Other)>
+        where M: Index<Key<OtherProvenance>, Output = Other>, F: Fn(&Value) -> Key<OtherProvenance> + 'a
+    {
+        self.iter_with_keys()
+            .map(move |(key, value)| (key, value, &other[foreign_key(value)]))
+    }
+}
+
+impl<Value: 'static> ProvenanceMap<Value> {
+
+    /// Get an [iterator](Iterator) over each value in the map together with its key,
+    /// and the value of another map it [refers to](Relation).
+    /// See [SeparateProvenanceMap::join](SeparateProvenanceMap::join).
+    /// ```
+    /// use provenance::{Key, ProvenanceMap, Relation};
+    ///
+    /// struct Currency(&'static str);
+    /// struct Money(i32, Key<Currency>);
+    ///
+    /// impl Relation<Currency> for Money {
+    ///     fn foreign_key(&self) -> Key<Currency> {
+    ///         self.1
+    ///     }
+    /// }
+    ///
+    /// let mut currencies = ProvenanceMap::<Currency>::new().unwrap();
+    /// let sek = currencies.insert(Currency("SEK"));
+    ///
+    /// let mut wallet = ProvenanceMap::<Money>::new().unwrap();
+    /// let key = wallet.insert(Money(5, sek));
+    ///
+    /// let (money, _, currency) = wallet.join(&currencies).next().unwrap();
+    /// assert_eq!((key, "SEK"), (money, currency.0));
+    /// ```
+    pub fn join<'a, OtherProvenance: 'static, Other: 'a, M>(&'a self, other: &'a M) -> impl Iterator<Item = (Key<Value>, &'a Value, &'a Other)>
+        where Value: Relation<OtherProvenance>, M: Index<Key<OtherProvenance>, Output = Other>
+    {
+        self.map.join(other)
+    }
+
+    /// Get an [iterator](Iterator) over each value in the map together with its key,
+    /// and the value of another map whose key is given by a function.
+    /// See [SeparateProvenanceMap::join_by](SeparateProvenanceMap::join_by).
+    /// ```
+    /// use provenance::{Key, ProvenanceMap};
+    ///
+    /// struct Currency(&'static str);
+    /// struct Money(i32, Key<Currency>);
+    ///
+    /// let mut currencies = ProvenanceMap::<Currency>::new().unwrap();
+    /// let sek = currencies.insert(Currency("SEK"));
+    ///
+    /// let mut wallet = ProvenanceMap::<Money>::new().unwrap();
+    /// wallet.insert(Money(5, sek));
+    /// wallet.insert(Money(10, sek));
+    ///
+    /// let total: i32 = wallet.join_by(&currencies, |money| money.1)
+    ///     .filter(|(_, _, currency)| currency.0 == "SEK")
+    ///     .map(|(_, money, _)| money.0)
+    ///     .sum();
+    /// assert_eq!(15, total);
+    /// ```
+    pub fn join_by<'a, OtherProvenance: 'static, Other: 'a, M, F>(&'a self, other: &'a M, foreign_key: F) -> impl Iterator<Item = (Key<Value>, &'a Value, &'a Other)>
+        where M: Index<Key<OtherProvenance>, Output = Other>, F: Fn(&Value) -> Key<OtherProvenance> + 'a
+    {
+        self.map.join_by(other, foreign_key)
+    }
+}
@@ -0,0 +1,595 @@
+//! Side tables that associate additional data with the keys of a map.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+};
+use crate::{Key, ProvenanceMap, SeparateProvenanceMap};
+
+/// A side table that associates values with [keys](Key) of some provenance.
+///
+/// It is meant for attaching data to values that are stored in another map.
+/// The table is backed by a vector indexed by the keys, which makes it far
+/// cheaper than hashing the keys.
+/// ```
+/// use provenance::{ProvenanceMap, SecondaryMap};
+///
+/// struct Node { name: &'static str }
+///
+/// let mut nodes = ProvenanceMap::<Node>::new().unwrap();
+/// let root = nodes.insert(Node { name: "root" });
+/// let leaf = nodes.insert(Node { name: "leaf" });
+///
+/// let mut depths = SecondaryMap::new();
+/// depths.insert(root, 0);
+/// depths.insert(leaf, 1);
+///
+/// assert_eq!(Some(&1), depths.get(leaf));
+/// ```
+///
+/// Entries are not removed when the value of their key is removed from its map.
+/// However, an entry is never mistaken for an entry of a value that later takes
+/// the place of the removed value.
+/// ```
+/// use provenance::{ProvenanceMap, SecondaryMap};
+///
+/// let mut map = ProvenanceMap::<i32>::new().unwrap();
+/// let mut names = SecondaryMap::new();
+///
+/// let old = map.insert(1);
+/// names.insert(old, "one");
+/// map.remove(old);
+///
+/// let new = map.insert(2);
+/// assert_eq!(None, names.get(new));
+/// ```
+pub struct SecondaryMap<Provenance, T> {
+    slots: Vec<Option<(Key<Provenance>, T)>>,
+    /// The slot of each key by its generation, for iterating in the [order](Key#ordering)
+    /// of the keys.
+    order: BTreeMap<u64, usize>,
+}
+
+impl<Provenance, T> SecondaryMap<Provenance, T> {
+
+    /// Create a new empty side table.
+    /// ```
+    /// use provenance::SecondaryMap;
+    ///
+    /// let map = SecondaryMap::<i32, String>::new();
+    /// assert_eq!(0, map.iter().count());
+    /// ```
+    pub fn new() -> SecondaryMap<Provenance, T> {
+        SecondaryMap {
+            slots: vec![],
+            order: BTreeMap::new(),
+        }
+    }
+
+    /// Associate a value with a key. If the key already had a value, it is replaced and returned.
+    /// ```
+    /// use provenance::{ProvenanceMap, SecondaryMap};
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// let mut names = SecondaryMap::new();
+    ///
+    /// let key = map.insert(1);
+    ///
+    /// assert_eq!(None, names.insert(key, "one"));
+    /// assert_eq!(Some("one"), names.insert(key, "ett"));
+    /// assert_eq!(Some(&"ett"), names.get(key));
+    /// ```
+    pub fn insert(&mut self, key: Key<Provenance>, value: T) -> Option<T> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    /// Use a [key](Key) to retrieve an immutable reference to the value associated with it, if any.
+    /// ```
+    /// use provenance::{ProvenanceMap, SecondaryMap};
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// let mut names = SecondaryMap::new();
+    ///
+    /// let one = map.insert(1);
+    /// let two = map.insert(2);
+    /// names.insert(one, "one");
+    ///
+    /// assert_eq!(Some(&"one"), names.get(one));
+    /// assert_eq!(None, names.get(two));
+    /// ```
+    pub fn get(&self, key: Key<Provenance>) -> Option<&T> {
+        match self.slots.get(key.index) {
+            Some(Some((occupant, value))) if *occupant == key => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to the value associated with it, if any.
+    /// ```
+    /// use provenance::{ProvenanceMap, SecondaryMap};
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// let mut names = SecondaryMap::new();
+    ///
+    /// let key = map.insert(1);
+    /// names.insert(key, String::from("one"));
+    ///
+    /// names.get_mut(key).unwrap().push('!');
+    /// assert_eq!("one!", names.get(key).unwrap());
+    /// ```
+    pub fn get_mut(&mut self, key: Key<Provenance>) -> Option<&mut T> {
+        match self.slots.get_mut(key.index) {
+            Some(Some((occupant, value))) if *occupant == key => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Check whether a value is associated with a [key](Key).
+    /// ```
+    /// use provenance::{ProvenanceMap, SecondaryMap};
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// let mut names = SecondaryMap::new();
+    ///
+    /// let key = map.insert(1);
+    /// assert!(!names.contains_key(key));
+    ///
+    /// names.insert(key, "one");
+    /// assert!(names.contains_key(key));
+    /// ```
+    pub fn contains_key(&self, key: Key<Provenance>) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Remove the value associated with a [key](Key), returning it.
+    ///
+    /// Unlike [entry](SecondaryMap::entry), this never grows the table, even for
+    /// keys that have no value in it.
+    /// ```
+    /// use provenance::{ProvenanceMap, SecondaryMap};
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// let mut names = SecondaryMap::new();
+    ///
+    /// let key = map.insert(1);
+    /// names.insert(key, "one");
+    ///
+    /// assert_eq!(Some("one"), names.remove(key));
+    /// assert_eq!(None, names.remove(key));
+    /// ```
+    pub fn remove(&mut self, key: Key<Provenance>) -> Option<T> {
+        let slot = self.slots.get_mut(key.index)
+            .filter(|slot| matches!(slot, Some((occupant, _)) if *occupant == key))?;
+
+        Some(OccupiedEntry { key, slot, order: &mut self.order }.remove())
+    }
+
+    /// Get the [entry](Entry) of a key, for in-place manipulation.
+    /// ```
+    /// use provenance::{ProvenanceMap, SecondaryMap};
+    /// let mut map = ProvenanceMap::<&str>::new().unwrap();
+    /// let mut visits = SecondaryMap::new();
+    ///
+    /// let home = map.insert("home");
+    /// let away = map.insert("away");
+    ///
+    /// for &page in [home, away, home].iter() {
+    ///     *visits.entry(page).or_insert(0) += 1;
+    /// }
+    ///
+    /// assert_eq!(Some(&2), visits.get(home));
+    /// assert_eq!(Some(&1), visits.get(away));
+    /// ```
+    pub fn entry(&mut self, key: Key<Provenance>) -> Entry<'_, Provenance, T> {
+        if self.slots.len() <= key.index {
+            self.slots.resize_with(key.index + 1, || None);
+        }
+
+        let slot = &mut self.slots[key.index];
+        let order = &mut self.order;
+        match slot {
+            Some((occupant, _)) if *occupant == key => Entry::Occupied(OccupiedEntry { key, slot, order }),
+            _ => Entry::Vacant(VacantEntry { key, slot, order }),
+        }
+    }
+
+    /// Get an [iterator](Iterator) over each key that has an associated value,
+    /// together with an immutable reference to that value.
+    /// The iterator visits the keys in [order](Key#ordering), i.e. in the order their
+    /// values were inserted into the map, even once a value has taken the place of
+    /// a removed one.
+    /// ```
+    /// use provenance::{ProvenanceMap, SecondaryMap};
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// let mut names = SecondaryMap::new();
+    ///
+    /// let removed = map.insert(0);
+    /// let one = map.insert(1);
+    /// map.remove(removed);
+    /// let two = map.insert(2); // Takes the place of 0
+    /// names.insert(two, "two");
+    /// names.insert(one, "one");
+    ///
+    /// let entries: Vec<_> = names.iter().collect();
+    /// assert_eq!(vec![(one, &"one"), (two, &"two")], entries);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (Key<Provenance>, &T)> {
+        self.order.values()
+            .filter_map(move |&index| self.slots[index].as_ref())
+            .map(|(key, value)| (*key, value))
+    }
+
+    /// Get an [iterator](Iterator) over each key that has an associated value,
+    /// together with a mutable reference to that value.
+    /// The iterator visits the keys in [order](Key#ordering), like [iter](SecondaryMap::iter).
+    /// ```
+    /// use provenance::{ProvenanceMap, SecondaryMap};
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// let mut counts = SecondaryMap::new();
+    ///
+    /// let one = map.insert(1);
+    /// let two = map.insert(2);
+    /// counts.insert(one, 10);
+    /// counts.insert(two, 20);
+    ///
+    /// counts.iter_mut().for_each(|(_, count)| *count += 1);
+    /// assert_eq!(Some(&21), counts.get(two));
+    /// ```
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Key<Provenance>, &mut T)> {
+        // The slots are borrowed one by one, and then handed out in the order of their keys.
+        let mut slots: Vec<_> = self.slots.iter_mut().map(Option::as_mut).collect();
+
+        self.order.values()
+            .filter_map(move |&index| slots[index].take())
+            .map(|(key, value)| (*key, value))
+    }
+
+    /// Turn this side table into a [complete](CompleteSecondaryMap) one for the given map.
+    /// Keys of the map that lack a value are given one by the provided function.
+    /// ```
+    /// use provenance::{SecondaryMap, SeparateProvenanceMap};
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// let mut squares = SecondaryMap::new();
+    ///
+    /// let two = map.insert(2);
+    /// squares.insert(two, 4);
+    ///
+    /// // The side table is kept in sync by completing it after inserting into the map
+    /// let three = map.insert(3);
+    /// let squares = squares.complete(&map, |_, value| value * value);
+    ///
+    /// assert_eq!(&4, squares.get(two));
+    /// assert_eq!(&9, squares.get(three));
+    /// ```
+    pub fn complete<'m, Value, F>(mut self, map: &'m SeparateProvenanceMap<Provenance, Value>, mut fill: F) -> CompleteSecondaryMap<'m, Provenance, T>
+        where Provenance: 'static, Value: 'static, F: FnMut(Key<Provenance>, &Value) -> T
+    {
+        for (key, value) in map.entries() {
+            self.entry(key).or_insert_with(|| fill(key, value));
+        }
+
+        CompleteSecondaryMap {
+            table: self,
+            _map: Default::default(),
+        }
+    }
+}
+
+impl<Provenance, T> Default for SecondaryMap<Provenance, T> {
+    fn default() -> Self {
+        SecondaryMap::new()
+    }
+}
+
+impl<Provenance, T: Debug> Debug for SecondaryMap<Provenance, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// A view into a single entry of a [SecondaryMap](SecondaryMap), which may either be
+/// vacant or occupied.
+///
+/// Constructed by [SecondaryMap::entry](SecondaryMap::entry).
+pub enum Entry<'a, Provenance, T> {
+    /// The key has an associated value.
+    Occupied(OccupiedEntry<'a, Provenance, T>),
+    /// The key does not have an associated value.
+    Vacant(VacantEntry<'a, Provenance, T>),
+}
+
+impl<'a, Provenance, T> Entry<'a, Provenance, T> {
+    /// The key of this entry.
+    pub fn key(&self) -> Key<Provenance> {
+        match self {
+            Entry::Occupied(entry) => entry.key,
+            Entry::Vacant(entry) => entry.key,
+        }
+    }
+
+    /// Ensure the key has a value by inserting the given one if vacant.
+    /// A mutable reference to the value is returned.
+    pub fn or_insert(self, default: T) -> &'a mut T {
+        self.or_insert_with(|| default)
+    }
+
+    /// Ensure the key has a value by inserting the result of the given function if vacant.
+    /// A mutable reference to the value is returned.
+    pub fn or_insert_with<F: FnOnce() -> T>(self, default: F) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Modify the value of the key in place, if it has one.
+    pub fn and_modify<F: FnOnce(&mut T)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, Provenance, T: Default> Entry<'a, Provenance, T> {
+    /// Ensure the key has a value by inserting the default value if vacant.
+    /// A mutable reference to the value is returned.
+    pub fn or_default(self) -> &'a mut T {
+        self.or_insert_with(T::default)
+    }
+}
+
+/// An occupied [entry](Entry).
+pub struct OccupiedEntry<'a, Provenance, T> {
+    key: Key<Provenance>,
+    slot: &'a mut Option<(Key<Provenance>, T)>,
+    order: &'a mut BTreeMap<u64, usize>,
+}
+
+impl<'a, Provenance, T> OccupiedEntry<'a, Provenance, T> {
+    /// The key of this entry.
+    pub fn key(&self) -> Key<Provenance> {
+        self.key
+    }
+
+    /// An immutable reference to the value of this entry.
+    pub fn get(&self) -> &T {
+        // The entry is only created for occupied slots.
+        &self.slot.as_ref().unwrap().1
+    }
+
+    /// A mutable reference to the value of this entry.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.slot.as_mut().unwrap().1
+    }
+
+    /// Convert the entry into a mutable reference to its value.
+    pub fn into_mut(self) -> &'a mut T {
+        &mut self.slot.as_mut().unwrap().1
+    }
+
+    /// Replace the value of this entry, returning the old one.
+    pub fn insert(&mut self, value: T) -> T {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// Remove the value of this entry, returning it.
+    pub fn remove(self) -> T {
+        self.order.remove(&self.key.generation);
+        self.slot.take().unwrap().1
+    }
+}
+
+/// A vacant [entry](Entry).
+pub struct VacantEntry<'a, Provenance, T> {
+    key: Key<Provenance>,
+    slot: &'a mut Option<(Key<Provenance>, T)>,
+    order: &'a mut BTreeMap<u64, usize>,
+}
+
+impl<'a, Provenance, T> VacantEntry<'a, Provenance, T> {
+    /// The key of this entry.
+    pub fn key(&self) -> Key<Provenance> {
+        self.key
+    }
+
+    /// Give the key of this entry a value.
+    /// A mutable reference to the value is returned.
+    pub fn insert(self, value: T) -> &'a mut T {
+        // The slot may still hold the value of a key whose value since has been
+        // removed from the map, which is replaced.
+        if let Some((replaced, _)) = self.slot {
+            self.order.remove(&replaced.generation);
+        }
+        self.order.insert(self.key.generation, self.key.index);
+
+        &mut self.slot.insert((self.key, value)).1
+    }
+}
+
+/// A side table that is guaranteed to associate a value with every key of a map.
+///
+/// The table borrows the map it covers, so no value can be inserted into, or
+/// removed from, that map while the table exists. Hence, retrieving a value
+/// from the table with a key of the map never fails, just like retrieving it
+/// from the map itself.
+/// ```
+/// use provenance::{CompleteSecondaryMap, SeparateProvenanceMap};
+///
+/// struct Currency { name: &'static str }
+/// struct Currencies;
+///
+/// let mut currencies = SeparateProvenanceMap::<Currencies, Currency>::new().unwrap();
+/// let sek = currencies.insert(Currency { name: "Swedish Krona" });
+///
+/// let lengths = CompleteSecondaryMap::new(&currencies, |_, currency| currency.name.len());
+///
+/// // Notice that the retrieved value is not wrapped in an Option
+/// assert_eq!(&13, lengths.get(sek));
+/// ```
+///
+/// Inserting into the map while the table exists is a compile time error:
+/// ```compile_fail
+/// use provenance::{CompleteSecondaryMap, SeparateProvenanceMap};
+/// struct Provenance;
+/// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+///
+/// let table = CompleteSecondaryMap::new(&map, |_, value| value * 2);
+/// let key = map.insert(5); // The map is borrowed by the table
+/// table.get(key);
+/// ```
+///
+/// To insert into the map, the table may be turned [back](CompleteSecondaryMap::into_inner)
+/// into a [SecondaryMap](SecondaryMap), and later [completed](SecondaryMap::complete) again.
+///
+/// Tables of a [ProvenanceMap](ProvenanceMap) are created and completed through the map,
+/// with [complete_secondary](ProvenanceMap::complete_secondary) and [complete](ProvenanceMap::complete).
+pub struct CompleteSecondaryMap<'m, Provenance, T> {
+    table: SecondaryMap<Provenance, T>,
+    _map: PhantomData<&'m ()>,
+}
+
+impl<'m, Provenance: 'static, T> CompleteSecondaryMap<'m, Provenance, T> {
+
+    /// Create a side table for a map, where the value of each key is given by the provided function.
+    /// ```
+    /// use provenance::{CompleteSecondaryMap, SeparateProvenanceMap};
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// let doubled = CompleteSecondaryMap::new(&map, |_, value| value * 2);
+    ///
+    /// assert_eq!(&10, doubled.get(key));
+    /// ```
+    pub fn new<Value, F>(map: &'m SeparateProvenanceMap<Provenance, Value>, fill: F) -> CompleteSecondaryMap<'m, Provenance, T>
+        where Value: 'static, F: FnMut(Key<Provenance>, &Value) -> T
+    {
+        SecondaryMap::new().complete(map, fill)
+    }
+
+    /// Use a [key](Key) to retrieve an immutable reference to the value associated with it.
+    /// ```
+    /// use provenance::{CompleteSecondaryMap, SeparateProvenanceMap};
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// let doubled = CompleteSecondaryMap::new(&map, |_, value| value * 2);
+    ///
+    /// assert_eq!(&10, doubled.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value of the key were removed from the map before the table were created.
+    pub fn get(&self, key: Key<Provenance>) -> &T {
+        // Every key that has a value in the map has a value in the table,
+        // and the map can not change while the table borrows it.
+        self.table.get(key).expect(crate::REMOVED_VALUE)
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to the value associated with it.
+    /// ```
+    /// use provenance::{CompleteSecondaryMap, SeparateProvenanceMap};
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// let mut doubled = CompleteSecondaryMap::new(&map, |_, value| value * 2);
+    ///
+    /// *doubled.get_mut(key) += 1;
+    /// assert_eq!(&11, doubled.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value of the key were removed from the map before the table were created.
+    pub fn get_mut(&mut self, key: Key<Provenance>) -> &mut T {
+        self.table.get_mut(key).expect(crate::REMOVED_VALUE)
+    }
+
+    /// Get an [iterator](Iterator) over each key with an immutable reference to its value.
+    /// The iterator visits the keys in [order](Key#ordering), like [SecondaryMap::iter].
+    /// ```
+    /// use provenance::{CompleteSecondaryMap, SeparateProvenanceMap};
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// let doubled = CompleteSecondaryMap::new(&map, |_, value| value * 2);
+    ///
+    /// assert_eq!(6, doubled.iter().map(|(_, value)| value).sum());
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (Key<Provenance>, &T)> {
+        self.table.iter()
+    }
+
+    /// Release the borrow of the map by turning this table into a plain [SecondaryMap](SecondaryMap).
+    /// ```
+    /// use provenance::{CompleteSecondaryMap, SeparateProvenanceMap};
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let five = map.insert(5);
+    /// let doubled = CompleteSecondaryMap::new(&map, |_, value| value * 2).into_inner();
+    ///
+    /// let six = map.insert(6);
+    /// assert_eq!(Some(&10), doubled.get(five));
+    /// assert_eq!(None, doubled.get(six));
+    /// ```
+    pub fn into_inner(self) -> SecondaryMap<Provenance, T> {
+        self.table
+    }
+}
+
+impl<Value: 'static> ProvenanceMap<Value> {
+
+    /// Create a [complete side table](CompleteSecondaryMap) for this map, where the value
+    /// of each key is given by the provided function.
+    /// See [CompleteSecondaryMap::new](CompleteSecondaryMap::new).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// let doubled = map.complete_secondary(|_, value| value * 2);
+    ///
+    /// assert_eq!(&10, doubled.get(key));
+    /// ```
+    pub fn complete_secondary<T, F>(&self, fill: F) -> CompleteSecondaryMap<'_, Value, T>
+        where F: FnMut(Key<Value>, &Value) -> T
+    {
+        CompleteSecondaryMap::new(&self.map, fill)
+    }
+
+    /// Turn a side table into a [complete](CompleteSecondaryMap) one for this map.
+    /// See [SecondaryMap::complete](SecondaryMap::complete).
+    /// ```
+    /// use provenance::{ProvenanceMap, SecondaryMap};
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// let mut squares = SecondaryMap::new();
+    ///
+    /// let two = map.insert(2);
+    /// squares.insert(two, 4);
+    ///
+    /// let three = map.insert(3);
+    /// let squares = map.complete(squares, |_, value| value * value);
+    ///
+    /// assert_eq!(&4, squares.get(two));
+    /// assert_eq!(&9, squares.get(three));
+    /// ```
+    pub fn complete<T, F>(&self, table: SecondaryMap<Value, T>, fill: F) -> CompleteSecondaryMap<'_, Value, T>
+        where F: FnMut(Key<Value>, &Value) -> T
+    {
+        table.complete(&self.map, fill)
+    }
+}
+
+impl<'m, Provenance, T: Debug> Debug for CompleteSecondaryMap<'m, Provenance, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.table.fmt(f)
+    }
+}
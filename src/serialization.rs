@@ -0,0 +1,308 @@
+//! Serialization of maps and keys with [serde](serde).
+//!
+//! A map is serialized as a sequence with one element per slot of the map, where
+//! slots whose value has been removed are serialized as none, and other slots as
+//! the generation and value of the slot. A key is serialized as the index of its
+//! slot, i.e. its position in that sequence, together with its generation.
+//!
+//! Since any key could be written in serialized data, a key may only be
+//! deserialized within the _key context_ of a map, see
+//! [SeparateProvenanceMap::key_context](SeparateProvenanceMap::key_context). The
+//! key is then checked against that map, so that a deserialized key always
+//! is valid for it. As the generation is checked too, a key whose value were
+//! removed before the map were serialized is rejected, rather than taken as the
+//! key of a later value in the same slot.
+
+use std::{
+    any::TypeId,
+    cell::RefCell,
+    convert::TryFrom,
+    fmt::{Debug, Formatter},
+};
+use serde::{
+    de::Error,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use crate::{Key, ProvenanceMap, SeparateProvenanceMap};
+
+/// Translate the index and generation of a serialized key into those of a key,
+/// if the serialized key is valid.
+type Lookup = unsafe fn(*const (), usize, u64) -> Option<(usize, u64)>;
+
+/// Something keys of a provenance may be checked against during deserialization.
+struct KeyContext {
+    provenance: TypeId,
+    source: *const (),
+    lookup: Lookup,
+}
+
+thread_local! {
+    /// The key contexts in use on this thread, innermost last.
+    static KEY_CONTEXTS: RefCell<Vec<KeyContext>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Run a function with a key context, removing the context afterwards even if the function panics.
+fn with_key_context<R, F: FnOnce() -> R>(context: KeyContext, f: F) -> R {
+    struct Pop;
+
+    impl Drop for Pop {
+        fn drop(&mut self) {
+            KEY_CONTEXTS.with(|contexts| contexts.borrow_mut().pop());
+        }
+    }
+
+    KEY_CONTEXTS.with(|contexts| contexts.borrow_mut().push(context));
+    let _pop = Pop;
+    f()
+}
+
+/// Look up a serialized key in the innermost key context for a provenance.
+fn lookup<Provenance: 'static>(index: usize, generation: u64) -> Result<Option<Key<Provenance>>, &'static str> {
+    KEY_CONTEXTS.with(|contexts| {
+        let contexts = contexts.borrow();
+        let context = contexts.iter()
+            .rev()
+            .find(|context| context.provenance == TypeId::of::<Provenance>())
+            .ok_or("a key may only be deserialized within the key context of a map")?;
+
+        // The source is borrowed by `with_key_context` for as long as the context exists,
+        // and `lookup` were created for the type of the source.
+        let found = unsafe { (context.lookup)(context.source, index, generation) };
+        Ok(found.map(|(index, generation)| Key::new(index, generation)))
+    })
+}
+
+impl<Provenance> Serialize for Key<Provenance> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.index as u64, self.generation).serialize(serializer)
+    }
+}
+
+impl<'de, Provenance: 'static> Deserialize<'de> for Key<Provenance> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (index, generation) = <(u64, u64)>::deserialize(deserializer)?;
+        let index = usize::try_from(index)
+            .map_err(|_| D::Error::custom(format_args!("key index {} is out of range", index)))?;
+
+        lookup(index, generation)
+            .map_err(D::Error::custom)?
+            .ok_or_else(|| D::Error::custom(format_args!("key index {} does not reference a value in the map", index)))
+    }
+}
+
+impl<Provenance, Value: Serialize> Serialize for SeparateProvenanceMap<Provenance, Value> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.slots.iter().map(|slot| slot.value.as_ref().map(|value| (slot.generation, value))))
+    }
+}
+
+impl<Value: Serialize> Serialize for ProvenanceMap<Value> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.map.serialize(serializer)
+    }
+}
+
+impl<Provenance: 'static, Value: 'static> SeparateProvenanceMap<Provenance, Value> {
+
+    /// Deserialize a serialized map, inserting its values into this map.
+    ///
+    /// Since the values get new keys when inserted, the keys that referenced them in
+    /// the serialized map are no longer valid. The returned [Remap](Remap) translates
+    /// such keys into the new ones. If deserialization fails, no value is inserted.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    ///
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, String>::new().unwrap();
+    /// let sek = map.insert("SEK".to_string());
+    ///
+    /// let json = serde_json::to_string(&map).unwrap();
+    /// let key_json = serde_json::to_string(&sek).unwrap();
+    /// drop(map);
+    ///
+    /// let mut map = SeparateProvenanceMap::<Provenance, String>::new().unwrap();
+    /// map.insert("NOK".to_string());
+    ///
+    /// let remap = map.deserialize_extend(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+    /// let sek = remap.key_context(|| serde_json::from_str(&key_json)).unwrap();
+    ///
+    /// assert_eq!("SEK", map.get(sek));
+    /// ```
+    pub fn deserialize_extend<'de, D>(&mut self, deserializer: D) -> Result<Remap<Provenance>, D::Error>
+        where D: Deserializer<'de>, Value: Deserialize<'de>
+    {
+        let values = Vec::<Option<(u64, Value)>>::deserialize(deserializer)?;
+
+        let keys = values.into_iter()
+            .map(|value| value.map(|(generation, value)| (generation, self.insert(value))))
+            .collect();
+
+        Ok(Remap { keys })
+    }
+
+    /// Run a function within the key context of this map.
+    ///
+    /// Keys deserialized by the function are checked against this map, and
+    /// deserialization fails if a key does not reference a value in the map.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// struct Currencies;
+    /// let mut currencies = SeparateProvenanceMap::<Currencies, &str>::new().unwrap();
+    /// let sek = currencies.insert("SEK");
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Money { amount: i32, currency: provenance::Key<Currencies> }
+    ///
+    /// let json = serde_json::to_string(&Money { amount: 5, currency: sek }).unwrap();
+    /// let money: Money = currencies.key_context(|| serde_json::from_str(&json)).unwrap();
+    /// assert_eq!(sek, money.currency);
+    ///
+    /// // An index that does not reference a value is rejected
+    /// let forged = r#"{ "amount": 5, "currency": [53, 0] }"#;
+    /// let money = currencies.key_context(|| serde_json::from_str::<Money>(forged));
+    /// assert!(money.is_err());
+    /// ```
+    ///
+    /// A key whose value has been removed is rejected, even if another value has
+    /// taken its place in the map.
+    /// ```
+    /// use provenance::{Key, SeparateProvenanceMap};
+    ///
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let old = map.insert(5);
+    /// let json = serde_json::to_string(&old).unwrap();
+    /// map.remove(old);
+    /// map.insert(7); // Takes the place of 5
+    ///
+    /// assert!(map.key_context(|| serde_json::from_str::<Key<Provenance>>(&json)).is_err());
+    /// ```
+    ///
+    /// Outside of a key context, keys can not be deserialized at all.
+    /// ```
+    /// use provenance::Key;
+    ///
+    /// struct Provenance;
+    /// assert!(serde_json::from_str::<Key<Provenance>>("[0, 0]").is_err());
+    /// ```
+    pub fn key_context<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        unsafe fn lookup<Provenance, Value>(source: *const (), index: usize, generation: u64) -> Option<(usize, u64)> {
+            let map = &*(source as *const SeparateProvenanceMap<Provenance, Value>);
+            map.slots.get(index)
+                .filter(|slot| slot.generation == generation && slot.value.is_some())
+                .map(|slot| (index, slot.generation))
+        }
+
+        with_key_context(
+            KeyContext {
+                provenance: TypeId::of::<Provenance>(),
+                source: self as *const Self as *const (),
+                lookup: lookup::<Provenance, Value>,
+            },
+            f,
+        )
+    }
+}
+
+impl<Value: 'static> ProvenanceMap<Value> {
+
+    /// Deserialize a serialized map, inserting its values into this map.
+    /// See [SeparateProvenanceMap::deserialize_extend](SeparateProvenanceMap::deserialize_extend).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let remap = map.deserialize_extend(&mut serde_json::Deserializer::from_str("[[0, 1], null, [2, 3]]")).unwrap();
+    /// assert_eq!(4, remap.keys().map(|key| map.get(key)).sum::<i32>());
+    /// ```
+    pub fn deserialize_extend<'de, D>(&mut self, deserializer: D) -> Result<Remap<Value>, D::Error>
+        where D: Deserializer<'de>, Value: Deserialize<'de>
+    {
+        self.map.deserialize_extend(deserializer)
+    }
+
+    /// Run a function within the key context of this map.
+    /// See [SeparateProvenanceMap::key_context](SeparateProvenanceMap::key_context).
+    /// ```
+    /// use provenance::{Key, ProvenanceMap};
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// let key = map.insert(5);
+    ///
+    /// let json = serde_json::to_string(&key).unwrap();
+    /// let deserialized: Key<i32> = map.key_context(|| serde_json::from_str(&json)).unwrap();
+    /// assert_eq!(key, deserialized);
+    /// ```
+    pub fn key_context<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        self.map.key_context(f)
+    }
+}
+
+/// Translation from keys of a serialized map to the keys its values got when
+/// [deserialized](SeparateProvenanceMap::deserialize_extend) into another map.
+pub struct Remap<Provenance> {
+    /// For each slot of the serialized map, the generation of its value and the new key of it.
+    keys: Vec<Option<(u64, Key<Provenance>)>>,
+}
+
+impl<Provenance: 'static> Remap<Provenance> {
+
+    /// Get an [iterator](Iterator) over the new keys, in the order their values were serialized.
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let remap = map.deserialize_extend(&mut serde_json::Deserializer::from_str("[[0, 1], [1, 2]]")).unwrap();
+    /// let values: Vec<_> = remap.keys().map(|key| *map.get(key)).collect();
+    /// assert_eq!(vec![1, 2], values);
+    /// ```
+    pub fn keys(&self) -> impl Iterator<Item = Key<Provenance>> + '_ {
+        self.keys.iter().flatten().map(|&(_, key)| key)
+    }
+
+    /// Run a function within the key context of the deserialized map.
+    ///
+    /// Keys deserialized by the function are translated from the keys of the serialized
+    /// map, and deserialization fails if a key did not reference a value in that map
+    /// when it were serialized.
+    /// ```
+    /// use provenance::{Key, ProvenanceMap};
+    /// let mut map = ProvenanceMap::<&str>::new().unwrap();
+    /// map.insert("occupies the first slot");
+    ///
+    /// let remap = map.deserialize_extend(&mut serde_json::Deserializer::from_str(r#"[[7, "SEK"]]"#)).unwrap();
+    /// let sek: Key<&str> = remap.key_context(|| serde_json::from_str("[0, 7]")).unwrap();
+    /// assert_eq!(&"SEK", map.get(sek));
+    ///
+    /// // Neither a key of another slot, nor of an earlier value of the slot, is accepted
+    /// assert!(remap.key_context(|| serde_json::from_str::<Key<&str>>("[1, 7]")).is_err());
+    /// assert!(remap.key_context(|| serde_json::from_str::<Key<&str>>("[0, 6]")).is_err());
+    /// ```
+    pub fn key_context<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        unsafe fn lookup<Provenance>(source: *const (), index: usize, generation: u64) -> Option<(usize, u64)> {
+            let remap = &*(source as *const Remap<Provenance>);
+            remap.keys.get(index)
+                .copied()
+                .flatten()
+                .filter(|&(serialized, _)| serialized == generation)
+                .map(|(_, key)| (key.index, key.generation))
+        }
+
+        with_key_context(
+            KeyContext {
+                provenance: TypeId::of::<Provenance>(),
+                source: self as *const Self as *const (),
+                lookup: lookup::<Provenance>,
+            },
+            f,
+        )
+    }
+}
+
+impl<Provenance> Debug for Remap<Provenance> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.keys.iter().map(|key| key.map(|(_, key)| key))).finish()
+    }
+}
@@ -0,0 +1,374 @@
+//! Point in time copies of maps.
+
+use std::{
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+};
+use crate::{Key, ProvenanceMap, SeparateProvenanceMap, Slot};
+
+const MISSING_VALUE: &str = "key refers to a value that were not in the map when the snapshot were taken";
+const FOREIGN_SNAPSHOT: &str = "snapshot were taken of another map with the same provenance";
+
+/// A read-only copy of a [SeparateProvenanceMap](SeparateProvenanceMap), as it were
+/// when the copy were [taken](SeparateProvenanceMap::snapshot).
+///
+/// A snapshot shares the provenance of its map, so keys of the map may be used
+/// with the snapshot. Unlike a map, a snapshot does not claim its provenance, as
+/// values can not be inserted into it. Hence, any number of snapshots may exist.
+/// A snapshot may however only be [restored](SeparateProvenanceMap::restore) into
+/// the very map it were taken of.
+/// ```
+/// use provenance::SeparateProvenanceMap;
+///
+/// struct Prices;
+/// let mut prices = SeparateProvenanceMap::<Prices, i32>::new().unwrap();
+/// let bread = prices.insert(25);
+///
+/// let before = prices.snapshot();
+/// *prices.get_mut(bread) += 5;
+///
+/// assert_eq!(&25, before.get(bread));
+/// assert_eq!(&30, prices.get(bread));
+/// ```
+pub struct Snapshot<Provenance, Value> {
+    slots: Vec<Slot<Value>>,
+    instance: u64,
+    _pd: PhantomData<fn() -> Provenance>,
+}
+
+impl<Provenance: 'static, Value: 'static> Snapshot<Provenance, Value> {
+
+    /// Use a [key](Key) to retrieve an immutable reference to a value as it were
+    /// when the snapshot were taken.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// let snapshot = map.snapshot();
+    /// map.remove(key);
+    ///
+    /// assert_eq!(&5, snapshot.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value were not in the map when the snapshot were taken,
+    /// e.g. if it were inserted afterwards.
+    /// ```should_panic
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let snapshot = map.snapshot();
+    /// let key = map.insert(5);
+    ///
+    /// snapshot.get(key); // Panics since the value is newer than the snapshot
+    /// ```
+    pub fn get(&self, key: Key<Provenance>) -> &Value {
+        self.try_get(key).expect(MISSING_VALUE)
+    }
+
+    /// Use a [key](Key) to retrieve an immutable reference to a value as it were
+    /// when the snapshot were taken, if the value were in the map then.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let five = map.insert(5);
+    /// let snapshot = map.snapshot();
+    /// let six = map.insert(6);
+    ///
+    /// assert_eq!(Some(&5), snapshot.try_get(five));
+    /// assert_eq!(None, snapshot.try_get(six));
+    /// ```
+    pub fn try_get(&self, key: Key<Provenance>) -> Option<&Value> {
+        self.slots.get(key.index)
+            .filter(|slot| slot.generation == key.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    /// Get an [iterator](Iterator) over all keys in the snapshot, leaving out the
+    /// values inserted with [insert_owned](SeparateProvenanceMap::insert_owned)
+    /// like [SeparateProvenanceMap::keys].
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(1);
+    /// let snapshot = map.snapshot();
+    /// map.insert(2);
+    ///
+    /// assert_eq!(vec![key], snapshot.keys().collect::<Vec<_>>());
+    /// ```
+    pub fn keys(&self) -> impl Iterator<Item = Key<Provenance>> + '_ {
+        self.slots.iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.value.is_some() && !slot.owned)
+            .map(|(index, slot)| Key::new(index, slot.generation))
+    }
+
+    /// Get an [iterator](Iterator) over immutable references to each value in the snapshot.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// let snapshot = map.snapshot();
+    /// map.insert(3);
+    ///
+    /// assert_eq!(3, snapshot.iter().sum());
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = &Value> {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+
+    /// Get the number of values in the snapshot.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// let snapshot = map.snapshot();
+    /// map.insert(2);
+    ///
+    /// assert_eq!(1, snapshot.len());
+    /// ```
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Check whether the snapshot contains no values.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// assert!(map.snapshot().is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+impl<Provenance: 'static, Value: Clone + 'static> SeparateProvenanceMap<Provenance, Value> {
+
+    /// Take a [snapshot](Snapshot) of the map, copying every value in it.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, String>::new().unwrap();
+    ///
+    /// let key = map.insert("Hello".to_string());
+    /// let snapshot = map.snapshot();
+    /// map.get_mut(key).push_str(" world");
+    ///
+    /// assert_eq!("Hello", snapshot.get(key));
+    /// ```
+    pub fn snapshot(&self) -> Snapshot<Provenance, Value> {
+        Snapshot {
+            slots: self.slots.iter().map(Slot::clone).collect(),
+            instance: self.instance,
+            _pd: Default::default(),
+        }
+    }
+
+    /// Roll the map back to how it were when a [snapshot](Snapshot) were taken.
+    ///
+    /// Values removed since the snapshot are put back, with their old keys valid again,
+    /// and values inserted since are removed, with their keys becoming stale.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// let snapshot = map.snapshot();
+    ///
+    /// map.remove(one);
+    /// let two = map.insert(2);
+    ///
+    /// map.restore(snapshot);
+    /// assert_eq!(&1, map.get(one));
+    /// assert_eq!(None, map.try_get(two));
+    /// ```
+    ///
+    /// Values inserted with [insert_owned](SeparateProvenanceMap::insert_owned) are
+    /// never removed by a restore, as their owned keys guarantee that they are in the
+    /// map. Such values are rolled back if they were in the map when the snapshot were
+    /// taken, and are otherwise kept as they are. Values put back by a restore are
+    /// never owned, as their owned keys have been given up.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let snapshot = map.snapshot();
+    /// let owned = map.insert_owned(1);
+    ///
+    /// map.restore(snapshot);
+    /// assert_eq!(1, map.remove_owned(owned));
+    /// ```
+    ///
+    /// Hence, a value in the snapshot whose slot has since been reused by an owned value
+    /// can not be put back, as the slot is taken. Such a value is dropped, and its key
+    /// stays stale.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// let snapshot = map.snapshot();
+    ///
+    /// map.remove(one);
+    /// let owned = map.insert_owned(2);
+    ///
+    /// map.restore(snapshot);
+    /// assert_eq!(None, map.try_get(one));
+    /// assert_eq!(&2, map.get_owned(&owned));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the snapshot were taken of another map, even one with the same
+    /// provenance, as that would make keys of the other map valid in this one.
+    /// ```should_panic
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    ///
+    /// let mut old = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// old.insert(1);
+    /// let snapshot = old.snapshot();
+    /// drop(old);
+    ///
+    /// let mut new = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// new.restore(snapshot); // Panics since the snapshot is of the old map
+    /// ```
+    pub fn restore(&mut self, snapshot: Snapshot<Provenance, Value>) {
+        assert_eq!(self.instance, snapshot.instance, "{}", FOREIGN_SNAPSHOT);
+
+        let len = self.slots.len().max(snapshot.slots.len());
+        let mut live = std::mem::take(&mut self.slots).into_iter();
+        let mut saved = snapshot.slots.into_iter();
+
+        self.slots = (0..len)
+            .map(|_| match (live.next(), saved.next()) {
+                (Some(live), saved) if live.owned && live.value.is_some() => match saved {
+                    Some(saved) if saved.generation == live.generation && saved.value.is_some() =>
+                        Slot { owned: true, ..saved },
+                    _ => live,
+                },
+                (_, Some(saved)) => Slot { owned: false, ..saved },
+                (Some(live), None) => Slot { owned: false, value: None, ..live },
+                (None, None) => unreachable!("both slots are missing below the longest length"),
+            })
+            .collect();
+
+        self.vacant = self.slots.iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.value.is_none())
+            .map(|(index, _)| index)
+            .collect();
+        self.order = self.slots.iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.value.is_some())
+            .map(|(index, slot)| (slot.generation, index))
+            .collect();
+    }
+}
+
+impl<Value: Clone + 'static> ProvenanceMap<Value> {
+
+    /// Take a [snapshot](Snapshot) of the map, copying every value in it.
+    /// See [SeparateProvenanceMap::snapshot](SeparateProvenanceMap::snapshot).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let key = map.insert(1);
+    /// let snapshot = map.snapshot();
+    /// *map.get_mut(key) += 1;
+    ///
+    /// assert_eq!(&1, snapshot.get(key));
+    /// ```
+    pub fn snapshot(&self) -> Snapshot<Value, Value> {
+        self.map.snapshot()
+    }
+
+    /// Roll the map back to how it were when a [snapshot](Snapshot) were taken.
+    /// See [SeparateProvenanceMap::restore](SeparateProvenanceMap::restore).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    ///
+    /// let key = map.insert(1);
+    /// let snapshot = map.snapshot();
+    /// *map.get_mut(key) += 1;
+    ///
+    /// map.restore(snapshot);
+    /// assert_eq!(&1, map.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the snapshot were taken of another map, like
+    /// [SeparateProvenanceMap::restore](SeparateProvenanceMap::restore).
+    pub fn restore(&mut self, snapshot: Snapshot<Value, Value>) {
+        self.map.restore(snapshot)
+    }
+}
+
+impl<Value: Clone> Clone for Slot<Value> {
+    fn clone(&self) -> Self {
+        Slot {
+            generation: self.generation,
+            owned: self.owned,
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<Provenance, Value: Clone> Clone for Snapshot<Provenance, Value> {
+
+    /// Copy the snapshot. Since snapshots do not claim their provenance, they may be
+    /// copied freely.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(1);
+    /// let snapshot = map.snapshot();
+    ///
+    /// *map.get_mut(key) += 1;
+    /// map.restore(snapshot.clone());
+    /// *map.get_mut(key) += 2;
+    /// map.restore(snapshot);
+    ///
+    /// assert_eq!(&1, map.get(key));
+    /// ```
+    fn clone(&self) -> Self {
+        Snapshot {
+            slots: self.slots.clone(),
+            instance: self.instance,
+            _pd: Default::default(),
+        }
+    }
+}
+
+impl<Provenance, Value: Debug> Debug for Snapshot<Provenance, Value> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let entries = self.slots.iter()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let key = Key::<Provenance>::new(index, slot.generation);
+                slot.value.as_ref().map(|value| (key, value))
+            });
+
+        f.debug_map().entries(entries).finish()
+    }
+}
@@ -0,0 +1,384 @@
+//! A map that records which of its values have changed.
+
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+    ops::Deref,
+};
+use crate::{Key, OwnedKey, ProvenanceError, SeparateProvenanceMap};
+
+/// A change to the value of a key, as reported by [TrackedProvenanceMap::drain_changes].
+pub enum ChangeEvent<Provenance> {
+    /// The value were inserted.
+    Inserted(Key<Provenance>),
+    /// The value may have been modified, as it were retrieved mutably.
+    Modified(Key<Provenance>),
+    /// The value were removed.
+    Removed(Key<Provenance>),
+}
+
+/// A [SeparateProvenanceMap](SeparateProvenanceMap) that records which keys have had
+/// their values inserted, modified or removed.
+///
+/// Values retrieved mutably are considered modified, whether they were changed or not.
+/// The changes are collected with [drain_changes](TrackedProvenanceMap::drain_changes).
+/// Tracking is opt-in, so a plain [SeparateProvenanceMap](SeparateProvenanceMap) has no
+/// such overhead.
+///
+/// Reading values records nothing, so the map dereferences to the underlying
+/// [SeparateProvenanceMap](SeparateProvenanceMap) for that. Only shared access is
+/// given, such that every change goes through this map and is recorded.
+/// ```
+/// use provenance::{ChangeEvent, TrackedProvenanceMap};
+///
+/// struct Widgets;
+/// let mut widgets = TrackedProvenanceMap::<Widgets, &str>::new().unwrap();
+///
+/// let button = widgets.insert("button");
+/// let label = widgets.insert("label");
+/// widgets.drain_changes().for_each(drop); // Render everything once
+///
+/// *widgets.get_mut(label) = "new label";
+/// assert_eq!(vec![ChangeEvent::Modified(label)], widgets.drain_changes().collect::<Vec<_>>());
+///
+/// // Nothing changed since the last drain
+/// assert_eq!(0, widgets.drain_changes().count());
+/// assert_eq!(&"button", widgets.get(button));
+/// ```
+pub struct TrackedProvenanceMap<Provenance, Value> {
+    map: SeparateProvenanceMap<Provenance, Value>,
+    /// Changes in the order they first were made, where a change that has been
+    /// cancelled out by a later change is none.
+    changes: Vec<Option<ChangeEvent<Provenance>>>,
+    /// Position of the change of each key in `changes`.
+    positions: HashMap<Key<Provenance>, usize>,
+}
+
+impl<Provenance: 'static, Value: 'static> TrackedProvenanceMap<Provenance, Value> {
+
+    /// Creates a new empty map with some type as provenance, with no changes recorded.
+    ///
+    /// The provenance is claimed like by [SeparateProvenanceMap::new](SeparateProvenanceMap::new),
+    /// so [`None`](std::option::Option::None) is returned if any map with such provenance exists.
+    /// ```
+    /// use provenance::{SeparateProvenanceMap, TrackedProvenanceMap};
+    /// struct Provenance;
+    ///
+    /// let mut map = TrackedProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// assert_eq!(0, map.drain_changes().count());
+    ///
+    /// assert!(SeparateProvenanceMap::<Provenance, i32>::new().is_none());
+    /// ```
+    pub fn new() -> Option<TrackedProvenanceMap<Provenance, Value>> {
+        TrackedProvenanceMap::try_new().ok()
+    }
+
+    /// Creates a new empty map with some type as provenance, with no changes recorded,
+    /// or an [error](ProvenanceError) of why it could not be created.
+    /// See [SeparateProvenanceMap::try_new](SeparateProvenanceMap::try_new).
+    /// ```
+    /// use provenance::{ProvenanceError, TrackedProvenanceMap};
+    ///
+    /// struct Documents;
+    /// fn open() -> Result<TrackedProvenanceMap<Documents, String>, ProvenanceError> {
+    ///     let mut documents = TrackedProvenanceMap::try_new()?;
+    ///     documents.insert("untitled".to_string());
+    ///     Ok(documents)
+    /// }
+    ///
+    /// let documents = open().unwrap();
+    /// assert_eq!(1, documents.len());
+    /// assert!(matches!(open(), Err(ProvenanceError::InUse { .. })));
+    /// ```
+    pub fn try_new() -> Result<TrackedProvenanceMap<Provenance, Value>, ProvenanceError> {
+        Ok(TrackedProvenanceMap {
+            map: SeparateProvenanceMap::try_new()?,
+            changes: vec![],
+            positions: HashMap::new(),
+        })
+    }
+
+    /// Insert a value into this map, recording its key as inserted.
+    /// ```
+    /// use provenance::{ChangeEvent, TrackedProvenanceMap};
+    /// struct Provenance;
+    /// let mut map = TrackedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// assert_eq!(vec![ChangeEvent::Inserted(key)], map.drain_changes().collect::<Vec<_>>());
+    /// ```
+    pub fn insert(&mut self, value: Value) -> Key<Provenance> {
+        let key = self.map.insert(value);
+        self.record(ChangeEvent::Inserted(key));
+        key
+    }
+
+    /// Insert a value into this map, and get an [owned key](OwnedKey) to it,
+    /// recording its key as inserted.
+    /// ```
+    /// use provenance::{ChangeEvent, TrackedProvenanceMap};
+    /// struct Provenance;
+    /// let mut map = TrackedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// assert_eq!(vec![ChangeEvent::Inserted(*owned.borrow())], map.drain_changes().collect::<Vec<_>>());
+    /// ```
+    pub fn insert_owned(&mut self, value: Value) -> OwnedKey<Provenance> {
+        let owned = self.map.insert_owned(value);
+        self.record(ChangeEvent::Inserted(owned.key));
+        owned
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to a stored value,
+    /// recording the key as modified.
+    /// ```
+    /// use provenance::{ChangeEvent, TrackedProvenanceMap};
+    /// struct Provenance;
+    /// let mut map = TrackedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// map.drain_changes().for_each(drop);
+    ///
+    /// *map.get_mut(key) += 1;
+    /// assert_eq!(vec![ChangeEvent::Modified(key)], map.drain_changes().collect::<Vec<_>>());
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value of the key has been removed, see [SeparateProvenanceMap::get_mut].
+    pub fn get_mut(&mut self, key: Key<Provenance>) -> &mut Value {
+        let value = self.map.get_mut(key);
+        record(&mut self.changes, &mut self.positions, ChangeEvent::Modified(key));
+        value
+    }
+
+    /// Use a [key](Key) to retrieve a mutable reference to a stored value, if the
+    /// value still is in the map, recording the key as modified if it is.
+    /// ```
+    /// use provenance::TrackedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = TrackedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// map.remove(key);
+    /// map.drain_changes().for_each(drop);
+    ///
+    /// assert_eq!(None, map.try_get_mut(key));
+    /// assert_eq!(0, map.drain_changes().count());
+    /// ```
+    pub fn try_get_mut(&mut self, key: Key<Provenance>) -> Option<&mut Value> {
+        let value = self.map.try_get_mut(key)?;
+        record(&mut self.changes, &mut self.positions, ChangeEvent::Modified(key));
+        Some(value)
+    }
+
+    /// Use an [owned key](OwnedKey) to retrieve a mutable reference to its value,
+    /// recording the key as modified.
+    /// ```
+    /// use provenance::TrackedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = TrackedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// map.drain_changes().for_each(drop);
+    ///
+    /// *map.get_owned_mut(&owned) += 1;
+    /// assert_eq!(1, map.drain_changes().count());
+    /// ```
+    ///
+    /// # Panics
+    /// Panics like [SeparateProvenanceMap::get_owned_mut].
+    pub fn get_owned_mut(&mut self, key: &OwnedKey<Provenance>) -> &mut Value {
+        let value = self.map.get_owned_mut(key);
+        record(&mut self.changes, &mut self.positions, ChangeEvent::Modified(key.key));
+        value
+    }
+
+    /// Get an [iterator](Iterator) over mutable references to each value in the map,
+    /// recording every key of the map as modified.
+    /// ```
+    /// use provenance::TrackedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = TrackedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.insert(1);
+    /// map.insert(2);
+    /// map.drain_changes().for_each(drop);
+    ///
+    /// map.iter_mut().for_each(|val| *val += 1);
+    /// assert_eq!(2, map.drain_changes().count());
+    /// ```
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        for (key, _) in self.map.entries() {
+            record(&mut self.changes, &mut self.positions, ChangeEvent::Modified(key));
+        }
+
+        self.map.iter_mut()
+    }
+
+    /// Remove a value from the map, recording its key as removed.
+    /// ```
+    /// use provenance::{ChangeEvent, TrackedProvenanceMap};
+    /// struct Provenance;
+    /// let mut map = TrackedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// map.drain_changes().for_each(drop);
+    ///
+    /// assert_eq!(5, map.remove(key));
+    /// assert_eq!(vec![ChangeEvent::Removed(key)], map.drain_changes().collect::<Vec<_>>());
+    /// ```
+    ///
+    /// # Panics
+    /// Panics like [SeparateProvenanceMap::remove].
+    pub fn remove(&mut self, key: Key<Provenance>) -> Value {
+        let value = self.map.remove(key);
+        self.record(ChangeEvent::Removed(key));
+        value
+    }
+
+    /// Remove a value inserted with [insert_owned](TrackedProvenanceMap::insert_owned),
+    /// recording its key as removed.
+    /// ```
+    /// use provenance::TrackedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = TrackedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let owned = map.insert_owned(5);
+    /// assert_eq!(5, map.remove_owned(owned));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics like [SeparateProvenanceMap::remove_owned].
+    pub fn remove_owned(&mut self, key: OwnedKey<Provenance>) -> Value {
+        let removed = key.key;
+        let value = self.map.remove_owned(key);
+        self.record(ChangeEvent::Removed(removed));
+        value
+    }
+
+    /// Take the changes made since they last were drained, in the order they were first made.
+    ///
+    /// Each key is reported at most once. A key that were inserted is reported as
+    /// inserted even if it since has been modified, and a key that were both inserted
+    /// and removed is not reported at all.
+    /// ```
+    /// use provenance::{ChangeEvent, TrackedProvenanceMap};
+    /// struct Provenance;
+    /// let mut map = TrackedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let one = map.insert(1);
+    /// let two = map.insert(2);
+    /// *map.get_mut(one) += 1;
+    /// map.remove(two);
+    ///
+    /// assert_eq!(vec![ChangeEvent::Inserted(one)], map.drain_changes().collect::<Vec<_>>());
+    /// ```
+    pub fn drain_changes(&mut self) -> impl Iterator<Item = ChangeEvent<Provenance>> + '_ {
+        self.positions.clear();
+        self.changes.drain(..).flatten()
+    }
+
+    fn record(&mut self, change: ChangeEvent<Provenance>) {
+        record(&mut self.changes, &mut self.positions, change)
+    }
+}
+
+/// Record a change, combining it with the earlier change of the same key, if any.
+///
+/// Takes the fields of the change log rather than the map, as modifications are
+/// recorded while the value about to be returned borrows the underlying map.
+fn record<Provenance>(
+    changes: &mut Vec<Option<ChangeEvent<Provenance>>>,
+    positions: &mut HashMap<Key<Provenance>, usize>,
+    change: ChangeEvent<Provenance>,
+) {
+    let key = change.key();
+
+    let position = match positions.get(&key) {
+        Some(&position) => position,
+        None => {
+            positions.insert(key, changes.len());
+            changes.push(Some(change));
+            return;
+        }
+    };
+
+    let earlier = &mut changes[position];
+    *earlier = match (*earlier, change) {
+        (Some(ChangeEvent::Inserted(_)), ChangeEvent::Removed(_)) => None,
+        (Some(ChangeEvent::Inserted(key)), _) => Some(ChangeEvent::Inserted(key)),
+        (_, change) => Some(change),
+    };
+}
+
+impl<Provenance> ChangeEvent<Provenance> {
+
+    /// The key whose value changed.
+    /// ```
+    /// use provenance::TrackedProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = TrackedProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.insert(5);
+    /// assert_eq!(Some(key), map.drain_changes().next().map(|change| change.key()));
+    /// ```
+    pub fn key(&self) -> Key<Provenance> {
+        match *self {
+            ChangeEvent::Inserted(key) => key,
+            ChangeEvent::Modified(key) => key,
+            ChangeEvent::Removed(key) => key,
+        }
+    }
+}
+
+impl<Provenance, Value> Deref for TrackedProvenanceMap<Provenance, Value> {
+    type Target = SeparateProvenanceMap<Provenance, Value>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<Provenance: 'static, Value: Debug + 'static> Debug for TrackedProvenanceMap<Provenance, Value> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+// Clone + Copy
+// Implemented manually, as deriving would require the provenance to implement them too.
+
+impl<Provenance> Clone for ChangeEvent<Provenance> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Provenance> Copy for ChangeEvent<Provenance> {}
+
+// PartialEq + Eq
+
+impl<Provenance> PartialEq for ChangeEvent<Provenance> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ChangeEvent::Inserted(a), ChangeEvent::Inserted(b)) => a == b,
+            (ChangeEvent::Modified(a), ChangeEvent::Modified(b)) => a == b,
+            (ChangeEvent::Removed(a), ChangeEvent::Removed(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<Provenance> Eq for ChangeEvent<Provenance> {}
+
+// Debug
+
+impl<Provenance> Debug for ChangeEvent<Provenance> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangeEvent::Inserted(key) => f.debug_tuple("Inserted").field(key).finish(),
+            ChangeEvent::Modified(key) => f.debug_tuple("Modified").field(key).finish(),
+            ChangeEvent::Removed(key) => f.debug_tuple("Removed").field(key).finish(),
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Changes to maps that are reverted unless committed.
+
+use std::marker::PhantomData;
+use crate::{Committed, Key, ProvenanceMap, SecondaryMap, SeparateProvenanceMap, SpeculativeKey};
+
+/// A change made through a [Transaction](Transaction), with what is needed to revert it.
+enum Change<Provenance, Value> {
+    Inserted(Key<Provenance>),
+    Modified(Key<Provenance>, Value),
+    Removed(Key<Provenance>, Value),
+}
+
+/// A set of changes to a map, which are reverted unless [committed](Transaction::commit).
+///
+/// A transaction is created with [SeparateProvenanceMap::transaction](SeparateProvenanceMap::transaction).
+/// Every change made through it is journaled, such that inserted values are removed,
+/// modified values are given back their old value, and removed values are put back
+/// if the transaction is dropped without being committed.
+///
+/// Values are accessed through [keys](SpeculativeKey) branded with the transaction,
+/// which can not be used after it has ended. Keys of the map are exchanged for such
+/// keys with [existing](Transaction::existing), and back again with the
+/// [Committed](Committed) returned when committing.
+pub struct Transaction<'tx, Provenance: 'static, Value: 'static> {
+    map: &'tx mut SeparateProvenanceMap<Provenance, Value>,
+    journal: Vec<Change<Provenance, Value>>,
+    /// Keys whose old value need not be journaled when modified, as it already has
+    /// been or as the value were inserted by the transaction.
+    journaled: SecondaryMap<Provenance, ()>,
+    _brand: PhantomData<fn(&'tx ()) -> &'tx ()>,
+}
+
+impl<Provenance: 'static, Value: Clone + 'static> SeparateProvenanceMap<Provenance, Value> {
+
+    /// Run the given closure as a [transaction](Transaction).
+    ///
+    /// To return [Ok](Ok), the closure must [commit](Transaction::commit) the transaction
+    /// and return the [Committed](Committed) it gets, together with its result. Returning
+    /// [Err](Err) or panicking before that reverts every change, so returning early with
+    /// `?` does too. Modifications are journaled by copying the old value, so the values
+    /// must be [Clone](Clone).
+    /// ```
+    /// use provenance::{Key, SeparateProvenanceMap};
+    ///
+    /// struct Accounts;
+    /// let mut accounts = SeparateProvenanceMap::<Accounts, i32>::new().unwrap();
+    /// let alice = accounts.insert(100);
+    /// let bob = accounts.insert(0);
+    ///
+    /// fn transfer(
+    ///     accounts: &mut SeparateProvenanceMap<Accounts, i32>,
+    ///     from: Key<Accounts>,
+    ///     to: Key<Accounts>,
+    ///     amount: i32,
+    /// ) -> Result<(), &'static str> {
+    ///     accounts.transaction(|mut tx| {
+    ///         let (from, to) = (tx.existing(from), tx.existing(to));
+    ///
+    ///         *tx.get_mut(to) += amount;
+    ///         *tx.get_mut(from) -= amount;
+    ///         if *tx.get(from) < 0 {
+    ///             return Err("insufficient funds"); // Both modifications are reverted
+    ///         }
+    ///
+    ///         Ok(((), tx.commit()))
+    ///     })
+    /// }
+    ///
+    /// assert!(transfer(&mut accounts, alice, bob, 60).is_ok());
+    /// assert!(transfer(&mut accounts, alice, bob, 60).is_err());
+    ///
+    /// assert_eq!((&40, &60), (accounts.get(alice), accounts.get(bob)));
+    /// ```
+    ///
+    /// Keys of values inserted through a transaction can not escape it, unless exchanged
+    /// for keys of the map once committed:
+    /// ```compile_fail
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.transaction(|mut tx| {
+    ///     let key = tx.insert(5);
+    ///     Ok::<_, ()>((key, tx.commit()))
+    /// });
+    /// ```
+    ///
+    /// Nor can the closure return [Ok](Ok) without committing:
+    /// ```compile_fail
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.transaction(|mut tx| {
+    ///     tx.insert(5);
+    ///     Ok::<_, ()>(())
+    /// });
+    /// ```
+    pub fn transaction<R, E, F>(&mut self, f: F) -> Result<R, E>
+        where F: for<'tx> FnOnce(Transaction<'tx, Provenance, Value>) -> Result<(R, Committed<'tx, Provenance>), E>
+    {
+        let tx = Transaction {
+            map: self,
+            journal: vec![],
+            journaled: SecondaryMap::new(),
+            _brand: Default::default(),
+        };
+
+        f(tx).map(|(result, _)| result)
+    }
+}
+
+impl<Value: Clone + 'static> ProvenanceMap<Value> {
+
+    /// Run the given closure as a [transaction](Transaction).
+    /// See [SeparateProvenanceMap::transaction](SeparateProvenanceMap::transaction).
+    /// ```
+    /// use provenance::ProvenanceMap;
+    /// let mut map = ProvenanceMap::<i32>::new().unwrap();
+    /// let key = map.insert(1);
+    ///
+    /// let result = map.transaction(|mut tx| {
+    ///     let key = tx.existing(key);
+    ///     *tx.get_mut(key) += 1;
+    ///     Err("reverted")
+    /// });
+    ///
+    /// assert_eq!(Err::<(), _>("reverted"), result);
+    /// assert_eq!(&1, map.get(key));
+    /// ```
+    pub fn transaction<R, E, F>(&mut self, f: F) -> Result<R, E>
+        where F: for<'tx> FnOnce(Transaction<'tx, Value, Value>) -> Result<(R, Committed<'tx, Value>), E>
+    {
+        self.map.transaction(f)
+    }
+}
+
+impl<'tx, Provenance: 'static, Value: Clone + 'static> Transaction<'tx, Provenance, Value> {
+
+    /// Insert a value into the map, until the transaction is reverted.
+    /// A key branded with the transaction is returned.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let _: Result<(), _> = map.transaction(|mut tx| {
+    ///     let key = tx.insert(5);
+    ///     assert_eq!(&5, tx.get(key));
+    ///     Err("reverted")
+    /// });
+    /// assert!(map.is_empty());
+    /// ```
+    pub fn insert(&mut self, value: Value) -> SpeculativeKey<'tx, Provenance> {
+        let key = self.map.insert(value);
+        self.journal.push(Change::Inserted(key));
+        self.journaled.insert(key, ());
+
+        Key::new(key.index, key.generation)
+    }
+
+    /// Exchange a key of the map for a key branded with the transaction, through
+    /// which its value may be accessed.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// let key = map.insert(5);
+    ///
+    /// map.transaction(|mut tx| {
+    ///     let key = tx.existing(key);
+    ///     assert_eq!(&5, tx.get(key));
+    ///     Ok::<_, ()>(((), tx.commit()))
+    /// });
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the key is stale, see [SeparateProvenanceMap::get](SeparateProvenanceMap::get).
+    pub fn existing(&self, key: Key<Provenance>) -> SpeculativeKey<'tx, Provenance> {
+        self.map.get(key);
+        Key::new(key.index, key.generation)
+    }
+
+    /// Use a [key](SpeculativeKey) to retrieve an immutable reference to a value.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// map.transaction(|mut tx| {
+    ///     let key = tx.insert(5);
+    ///     assert_eq!(&5, tx.get(key));
+    ///     Ok::<_, ()>(((), tx.commit()))
+    /// });
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value has been [removed](Transaction::remove) by the transaction.
+    pub fn get(&self, key: SpeculativeKey<'tx, Provenance>) -> &Value {
+        self.map.get(Key::new(key.index, key.generation))
+    }
+
+    /// Use a [key](SpeculativeKey) to retrieve a mutable reference to a value.
+    ///
+    /// The first time a value that were in the map before the transaction is retrieved,
+    /// a copy of it is journaled, such that it can be reverted.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, String>::new().unwrap();
+    /// let key = map.insert("Hello".to_string());
+    ///
+    /// let _: Result<(), _> = map.transaction(|mut tx| {
+    ///     let key = tx.existing(key);
+    ///     tx.get_mut(key).push_str(" world");
+    ///     assert_eq!("Hello world", tx.get(key));
+    ///     Err("reverted")
+    /// });
+    ///
+    /// assert_eq!("Hello", map.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value has been [removed](Transaction::remove) by the transaction.
+    pub fn get_mut(&mut self, key: SpeculativeKey<'tx, Provenance>) -> &mut Value {
+        let key = Key::new(key.index, key.generation);
+
+        if self.journaled.insert(key, ()).is_none() {
+            let old = self.map.get(key).clone();
+            self.journal.push(Change::Modified(key, old));
+        }
+
+        self.map.get_mut(key)
+    }
+
+    /// Use a [key](SpeculativeKey) to remove a value from the map, until the
+    /// transaction is reverted.
+    ///
+    /// The removed value is kept in the journal, to be put back if the transaction is
+    /// reverted, so only a reference to it is returned.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    /// let key = map.insert(5);
+    ///
+    /// let _: Result<(), _> = map.transaction(|mut tx| {
+    ///     let key = tx.existing(key);
+    ///     assert_eq!(&5, tx.remove(key));
+    ///     Err("reverted")
+    /// });
+    ///
+    /// assert_eq!(&5, map.get(key));
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the value already has been removed, or if it were inserted with
+    /// [insert_owned](SeparateProvenanceMap::insert_owned).
+    pub fn remove(&mut self, key: SpeculativeKey<'tx, Provenance>) -> &Value {
+        let key = Key::new(key.index, key.generation);
+        let value = self.map.remove(key);
+        self.journal.push(Change::Removed(key, value));
+
+        match self.journal.last() {
+            Some(Change::Removed(_, value)) => value,
+            _ => unreachable!("the removal were just journaled"),
+        }
+    }
+
+    /// End the transaction, keeping every change made through it.
+    ///
+    /// The returned [Committed](Committed) exchanges the keys of the transaction for
+    /// keys of the map, which may be used after the transaction has ended. It is also
+    /// what the closure of [transaction](SeparateProvenanceMap::transaction) must return
+    /// to succeed. Once committed, the changes are kept even if the closure then fails.
+    /// ```
+    /// use provenance::SeparateProvenanceMap;
+    /// struct Provenance;
+    /// let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    ///
+    /// let key = map.transaction(|mut tx| {
+    ///     let key = tx.insert(5);
+    ///     let committed = tx.commit();
+    ///     Ok::<_, ()>((committed.key(key), committed))
+    /// });
+    ///
+    /// assert_eq!(&5, map.get(key.unwrap()));
+    /// ```
+    pub fn commit(mut self) -> Committed<'tx, Provenance> {
+        self.journal.clear();
+        Committed::new()
+    }
+}
+
+impl<'tx, Provenance: 'static, Value: 'static> Drop for Transaction<'tx, Provenance, Value> {
+    fn drop(&mut self) {
+        // Reverting in reverse order makes each slot hold what it did when the change were made.
+        for change in self.journal.drain(..).rev() {
+            match change {
+                Change::Inserted(key) => { self.map.take(key); }
+                Change::Modified(key, old) => *self.map.get_mut(key) = old,
+                Change::Removed(key, value) => self.map.put_back(key, value),
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::{cell::Cell, rc::Rc};
+use provenance::AppendOnlyProvenanceMap;
+
+/// Counts how many times values are dropped.
+struct Counted(Rc<Cell<usize>>);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+#[test]
+fn references_stay_valid_across_buckets() {
+    struct Provenance;
+    let map = AppendOnlyProvenanceMap::<Provenance, String>::new().unwrap();
+
+    let first = map.get(map.insert("0".to_string()));
+    let last_of_bucket = map.get((1..32).map(|n| map.insert(n.to_string())).last().unwrap());
+    // Crosses into the buckets of 64, 128 and 256 values.
+    let keys: Vec<_> = (32..500).map(|n| map.insert(n.to_string())).collect();
+
+    assert_eq!("0", first);
+    assert_eq!("31", last_of_bucket);
+    for (n, key) in (32..).zip(keys) {
+        assert_eq!(&n.to_string(), map.get(key));
+    }
+}
+
+#[test]
+fn iterates_in_insertion_order_across_buckets() {
+    struct Provenance;
+    let mut map = AppendOnlyProvenanceMap::<Provenance, usize>::new().unwrap();
+    for n in 0..300 {
+        map.insert(n);
+    }
+
+    map.iter_mut().for_each(|value| *value *= 2);
+
+    assert_eq!((0..300).map(|n| n * 2).collect::<Vec<_>>(), map.iter().copied().collect::<Vec<_>>());
+    assert_eq!(300, map.keys().count());
+    assert_eq!(Some(&64), map.find(|&value| value == 64));
+}
+
+#[test]
+fn drops_every_value_once() {
+    struct Provenance;
+    let drops = Rc::new(Cell::new(0));
+    let map = AppendOnlyProvenanceMap::<Provenance, Counted>::new().unwrap();
+
+    // Ends in a partly filled bucket, whose vacant slots must not be dropped.
+    for _ in 0..100 {
+        map.insert(Counted(Rc::clone(&drops)));
+    }
+    assert_eq!(0, drops.get());
+
+    drop(map);
+    assert_eq!(100, drops.get());
+}
@@ -0,0 +1,62 @@
+use std::panic::{self, AssertUnwindSafe};
+use provenance::SeparateProvenanceMap;
+
+#[test]
+fn truncating_frees_reused_slots_again() {
+    struct Provenance;
+    let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    let removed = map.insert(1);
+    let kept = map.insert(2);
+    map.remove(removed);
+
+    map.checkpoint(|mut checkpoint| {
+        // Reuses the slot of the removed value, then grows the map.
+        let reused = checkpoint.insert(3);
+        let appended = checkpoint.insert(4);
+        assert_eq!((&3, &4), (checkpoint.get(reused), checkpoint.get(appended)));
+        checkpoint.truncate();
+    });
+
+    assert_eq!(vec![(kept, &2)], map.iter_with_keys().collect::<Vec<_>>());
+    assert_eq!(None, map.try_get(removed));
+
+    let a = map.insert(5);
+    let b = map.insert(6);
+    let c = map.insert(7);
+    assert_eq!(vec![&5, &2, &6, &7], map.iter().collect::<Vec<_>>());
+    assert_eq!(vec![a, kept, b, c], map.keys().collect::<Vec<_>>());
+}
+
+#[test]
+fn committing_keeps_values_in_reused_slots() {
+    struct Provenance;
+    let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    let removed = map.insert(1);
+    let kept = map.insert(3);
+    map.remove(removed);
+
+    let key = map.checkpoint(|mut checkpoint| {
+        let key = checkpoint.insert(2);
+        checkpoint.commit().key(key)
+    });
+
+    assert_eq!(None, map.try_get(removed));
+    assert_eq!(vec![(key, &2), (kept, &3)], map.iter_with_keys().collect::<Vec<_>>());
+}
+
+#[test]
+fn panicking_truncates_the_checkpoint() {
+    struct Provenance;
+    let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    let kept = map.insert(1);
+
+    let unwound = panic::catch_unwind(AssertUnwindSafe(|| {
+        map.checkpoint(|mut checkpoint| {
+            checkpoint.insert(2);
+            panic!("aborted");
+        })
+    }));
+
+    assert!(unwound.is_err());
+    assert_eq!(vec![(kept, &1)], map.iter_with_keys().collect::<Vec<_>>());
+}
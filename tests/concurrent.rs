@@ -0,0 +1,87 @@
+use std::{collections::HashSet, sync::atomic::{AtomicBool, Ordering}, thread};
+use provenance::ConcurrentProvenanceMap;
+
+const THREADS: usize = 8;
+const PER_THREAD: usize = 1000;
+
+#[test]
+fn inserts_from_many_threads_get_distinct_keys() {
+    struct Provenance;
+    let map = ConcurrentProvenanceMap::<Provenance, (usize, usize)>::new().unwrap();
+
+    let keys: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..THREADS)
+            .map(|thread| {
+                let map = &map;
+                scope.spawn(move || (0..PER_THREAD).map(|n| map.insert((thread, n))).collect::<Vec<_>>())
+            })
+            .collect();
+
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+
+    assert_eq!(THREADS * PER_THREAD, keys.iter().collect::<HashSet<_>>().len());
+    assert_eq!(THREADS * PER_THREAD, map.iter().count());
+    for (i, key) in keys.into_iter().enumerate() {
+        assert_eq!(&(i / PER_THREAD, i % PER_THREAD), map.get(key));
+    }
+}
+
+#[test]
+fn readers_only_see_initialised_values() {
+    struct Provenance;
+    let map = ConcurrentProvenanceMap::<Provenance, Vec<usize>>::new().unwrap();
+    let done = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let readers: Vec<_> = (0..2)
+            .map(|_| scope.spawn(|| {
+                let mut seen = 0;
+                while !done.load(Ordering::Acquire) {
+                    // A value whose entry were published before it were written would
+                    // show up here as an empty or partially written vector.
+                    for value in map.iter() {
+                        assert_eq!(16, value.len());
+                        assert!(value.iter().all(|&n| n == value[0]));
+                    }
+                    seen = seen.max(map.keys().count());
+                }
+                seen
+            }))
+            .collect();
+
+        let writers: Vec<_> = (0..THREADS)
+            .map(|_| scope.spawn(|| {
+                for n in 0..PER_THREAD {
+                    let key = map.insert(vec![n; 16]);
+                    assert_eq!(n, map.get(key)[15]);
+                }
+            }))
+            .collect();
+
+        writers.into_iter().for_each(|writer| writer.join().unwrap());
+        done.store(true, Ordering::Release);
+
+        for reader in readers {
+            assert!(reader.join().unwrap() <= THREADS * PER_THREAD);
+        }
+    });
+
+    assert_eq!(THREADS * PER_THREAD, map.iter().count());
+}
+
+#[test]
+fn values_stay_in_place_across_buckets() {
+    struct Provenance;
+    let map = ConcurrentProvenanceMap::<Provenance, usize>::new().unwrap();
+
+    let first = map.get(map.insert(0));
+    // Fills the buckets of 32, 64, 128, 256 and 512 values, and starts the next.
+    let keys: Vec<_> = (1..1000).map(|n| map.insert(n)).collect();
+
+    assert_eq!(&0, first);
+    for (n, key) in (1..).zip(keys) {
+        assert_eq!(&n, map.get(key));
+    }
+    assert_eq!((0..1000).collect::<Vec<_>>(), map.iter().copied().collect::<Vec<_>>());
+}
@@ -0,0 +1,54 @@
+use provenance::SeparateProvenanceMap;
+
+#[test]
+fn ranges_visit_reused_slots_in_insertion_order() {
+    struct Provenance;
+    let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    let removed = map.insert(1);
+    let second = map.insert(2);
+    let third = map.insert(3);
+    map.remove(removed);
+    let reused = map.insert(4); // Takes the place of 1
+
+    assert_eq!(vec![(second, &2), (third, &3), (reused, &4)], map.range(..).collect::<Vec<_>>());
+    assert_eq!(vec![third, reused], map.keys_after(second).collect::<Vec<_>>());
+    assert_eq!(vec![(third, &3)], map.range(third..reused).collect::<Vec<_>>());
+    assert_eq!((Some(second), Some(reused)), (map.first_key(), map.last_key()));
+
+    // A range ending before it starts is empty
+    assert_eq!(0, map.range(reused..second).count());
+}
+
+#[test]
+fn restoring_a_snapshot_restores_the_order() {
+    struct Provenance;
+    let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    let first = map.insert(1);
+    let second = map.insert(2);
+    let snapshot = map.snapshot();
+
+    map.remove(first);
+    map.insert(3);
+    map.restore(snapshot);
+
+    assert_eq!(vec![(first, &1), (second, &2)], map.range(..).collect::<Vec<_>>());
+    assert_eq!((Some(first), Some(second)), (map.first_key(), map.last_key()));
+}
+
+#[test]
+fn searches_follow_slot_order_once_a_value_is_removed() {
+    struct Provenance;
+    let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    let removed = map.insert(1);
+    let two = map.insert(2);
+    map.insert(3);
+    map.remove(removed);
+    let four = map.insert(4); // Takes the place of 1
+
+    assert_eq!(vec![&4, &2, &3], map.iter().collect::<Vec<_>>());
+    assert_eq!(Some(&4), map.find(|val| val % 2 == 0));
+    assert_eq!(Some(&mut 4), map.find_mut(|val| val % 2 == 0));
+    assert_eq!(Some(four), map.find_key(|val| val % 2 == 0));
+    assert_eq!(Some(0), map.position(|val| val % 2 == 0));
+    assert_eq!(vec![four, two], map.filter_keys(|val| val % 2 == 0).collect::<Vec<_>>());
+}
@@ -0,0 +1,75 @@
+use std::{cell::Cell, rc::Rc};
+use provenance::PersistentProvenanceMap;
+
+/// Counts how many times values are dropped.
+struct Counted(usize, Rc<Cell<usize>>);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.1.set(self.1.get() + 1);
+    }
+}
+
+#[test]
+fn grows_through_several_levels() {
+    struct Provenance;
+    let mut map = PersistentProvenanceMap::<Provenance, usize>::new().unwrap();
+    let mut versions = vec![];
+    let mut keys = vec![];
+
+    // Fills one and two levels of 32 children, and starts a third.
+    for n in 0..1100 {
+        let (next, key) = map.insert(n);
+        versions.push(map);
+        map = next;
+        keys.push(key);
+    }
+
+    assert_eq!((0..1100).collect::<Vec<_>>(), map.iter().copied().collect::<Vec<_>>());
+    assert_eq!(keys, map.keys().collect::<Vec<_>>());
+    for (len, version) in versions.iter().enumerate() {
+        assert_eq!(len, version.len());
+        assert_eq!(None, version.try_get(keys[len]));
+    }
+    for boundary in [31, 32, 1023, 1024] {
+        assert_eq!(&boundary, versions[boundary + 1].get(keys[boundary]));
+    }
+}
+
+#[test]
+fn set_leaves_other_versions_unchanged() {
+    struct Provenance;
+    let (mut map, first) = PersistentProvenanceMap::<Provenance, usize>::new().unwrap().insert(0);
+    for n in 1..100 {
+        map = map.insert(n).0;
+    }
+    let last = map.keys().last().unwrap();
+
+    let changed = map.set(first, 1000).set(last, 2000);
+
+    assert_eq!((&0, &99), (map.get(first), map.get(last)));
+    assert_eq!((&1000, &2000), (changed.get(first), changed.get(last)));
+    assert_eq!(map.iter().skip(1).take(98).collect::<Vec<_>>(), changed.iter().skip(1).take(98).collect::<Vec<_>>());
+}
+
+#[test]
+fn values_are_dropped_with_the_last_version_sharing_them() {
+    struct Provenance;
+    let drops = Rc::new(Cell::new(0));
+    let empty = PersistentProvenanceMap::<Provenance, Counted>::new().unwrap();
+
+    let (one, key) = empty.insert(Counted(1, Rc::clone(&drops)));
+    let two = one.set(key, Counted(2, Rc::clone(&drops)));
+    let three = two.insert(Counted(3, Rc::clone(&drops))).0;
+
+    assert_eq!(1, one.get(key).0);
+    assert_eq!(2, three.get(key).0);
+
+    drop(one);
+    assert_eq!(1, drops.get());
+    drop(two);
+    assert_eq!(1, drops.get());
+    drop(three);
+    assert_eq!(3, drops.get());
+    drop(empty);
+}
@@ -0,0 +1,94 @@
+use std::panic::{self, AssertUnwindSafe};
+use provenance::SeparateProvenanceMap;
+
+#[test]
+fn reverting_puts_back_a_value_whose_slot_were_reused() {
+    struct Provenance;
+    let mut map = SeparateProvenanceMap::<Provenance, String>::new().unwrap();
+    let first = map.insert("first".to_string());
+    let other = map.insert("other".to_string());
+
+    let result: Result<(), _> = map.transaction(|mut tx| {
+        let removed = tx.existing(first);
+        assert_eq!("first", tx.remove(removed));
+
+        // Reuses the slot of the removed value.
+        let second = tx.insert("second".to_string());
+        tx.get_mut(second).push_str(" modified");
+        Err("reverted")
+    });
+
+    assert!(result.is_err());
+    assert_eq!(vec![(first, "first"), (other, "other")], map.iter_with_keys().map(|(key, value)| (key, value.as_str())).collect::<Vec<_>>());
+
+    // The slot is not vacant, so a new value gets a slot of its own.
+    map.insert("third".to_string());
+    assert_eq!(vec!["first", "other", "third"], map.iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn committing_keeps_the_value_in_the_reused_slot() {
+    struct Provenance;
+    let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    let first = map.insert(1);
+    let other = map.insert(3);
+
+    let second = map.transaction(|mut tx| {
+        let removed = tx.existing(first);
+        tx.remove(removed);
+        let second = tx.insert(2);
+        let committed = tx.commit();
+        Ok::<_, ()>((committed.key(second), committed))
+    }).unwrap();
+
+    assert_eq!(None, map.try_get(first));
+    assert_eq!(vec![(second, &2), (other, &3)], map.iter_with_keys().collect::<Vec<_>>());
+}
+
+#[test]
+fn reverting_undoes_changes_to_values_inserted_and_removed_by_the_transaction() {
+    struct Provenance;
+    let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    let kept = map.insert(1);
+
+    let _: Result<(), _> = map.transaction(|mut tx| {
+        let kept = tx.existing(kept);
+        *tx.get_mut(kept) += 10;
+        *tx.get_mut(kept) += 10;
+
+        let inserted = tx.insert(2);
+        *tx.get_mut(inserted) += 10;
+        tx.remove(inserted);
+        tx.remove(kept);
+
+        // Both slots are vacant, and are reused in turn.
+        tx.insert(3);
+        tx.insert(4);
+        Err(())
+    });
+
+    assert_eq!(vec![(kept, &1)], map.iter_with_keys().collect::<Vec<_>>());
+    map.insert(5);
+    assert_eq!(vec![&1, &5], map.iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn panicking_reverts_the_transaction() {
+    struct Provenance;
+    let mut map = SeparateProvenanceMap::<Provenance, i32>::new().unwrap();
+    let first = map.insert(1);
+
+    let unwound = panic::catch_unwind(AssertUnwindSafe(|| {
+        map.transaction(|mut tx| {
+            let existing = tx.existing(first);
+            tx.remove(existing);
+            tx.insert(2);
+            panic!("aborted");
+            #[allow(unreachable_code)]
+            Ok::<_, ()>(((), tx.commit()))
+        })
+    }));
+
+    assert!(unwound.is_err());
+    assert_eq!(vec![(first, &1)], map.iter_with_keys().collect::<Vec<_>>());
+}